@@ -30,4 +30,41 @@ pub fn round_staking<'info>(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn presale_succeeded<'info>(
+    presale_state: &Account<'info, PresaleState>,
+    clock: &Sysvar<'info, Clock>,
+) -> Result<()> {
+    round_staking(presale_state, clock)?;
+
+    if !presale_state.is_soft_cap_reached() {
+        return err!(ErrorCode::SoftCapNotReached);
+    }
+
+    Ok(())
+}
+
+pub fn presale_failed<'info>(
+    presale_state: &Account<'info, PresaleState>,
+    clock: &Sysvar<'info, Clock>,
+) -> Result<()> {
+    round_staking(presale_state, clock)?;
+
+    if presale_state.is_soft_cap_reached() {
+        return err!(ErrorCode::SoftCapReached);
+    }
+
+    Ok(())
+}
+
+pub fn not_paused<'info>(
+    config: &Account<'info, Config>,
+    flag: u8,
+) -> Result<()> {
+    if config.is_paused(flag) {
+        return err!(ErrorCode::Paused);
+    }
+
+    Ok(())
+}
@@ -1,5 +1,49 @@
 use anchor_lang::prelude::*;
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorityRole {
+    Admin,
+    Vault,
+    Operator,
+    Treasury,
+    Guardian,
+}
+
+// bits of `Config::paused`, each halting one group of instructions
+pub const PAUSE_PRESALE: u8 = 1 << 0;
+pub const PAUSE_CREATE_NODES: u8 = 1 << 1;
+pub const PAUSE_CLAIMS: u8 = 1 << 2;
+pub const PAUSE_MINTING: u8 = 1 << 3;
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 4;
+pub const PAUSE_STAKE_CHANGES: u8 = 1 << 5;   // unstaking and transferring staked nodes
+pub const PAUSE_NODE_NFTS: u8 = 1 << 6;       // minting and burning node NFTs
+pub const PAUSE_ALL: u8 = PAUSE_PRESALE | PAUSE_CREATE_NODES | PAUSE_CLAIMS | PAUSE_MINTING
+    | PAUSE_WITHDRAWALS | PAUSE_STAKE_CHANGES | PAUSE_NODE_NFTS;
+
+#[account]
+pub struct Config {
+    pub admin: Pubkey,
+    pub vault_authority: Pubkey,
+    pub operator: Pubkey,           // runs presale rounds and funds rewards next to the admin
+    pub treasury: Pubkey,           // owns the treasury token accounts of every pool
+    pub pending_authority: Pubkey,  // all zeroes when no handover is in progress
+    pub pending_role: AuthorityRole,
+    pub guardian: Pubkey,           // can pause and unpause instructions
+    pub paused: u8,
+}
+
+impl Config {
+    pub const SPACE: usize = 32 * 6 + 1 + 1;
+
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.paused & flag != 0
+    }
+
+    pub fn is_operator(&self, key: &Pubkey) -> bool {
+        *key == self.admin || *key == self.operator
+    }
+}
+
 #[account]
 pub struct PresaleState {
     pub price_per_node: u64,
@@ -7,27 +51,95 @@ pub struct PresaleState {
     pub presale_start_at: i64,
     pub presale_end_at: i64,
     pub total_presale_amount: u16,
+    pub round_id: u8,
+    pub sold_amount: u16,
+    pub merkle_root: [u8; 32],      // all zeroes when the round is open to everyone
+    pub soft_cap: u64,              // minimum raised for the round to succeed, in the same units as price_per_node
+    pub raised_amount: u64,
+    pub is_withdrawn: bool,
+    pub is_finalized: bool,
+    pub referral_commission: u64,   // lamports owed to referrers once the round succeeds
+    pub usd_price_per_node: u64,    // micro dollars, zero when the round is priced in lamports
+    pub price_feed: Pubkey,         // SOL/USD price account used with `usd_price_per_node`
+    pub max_price_age: i64,
+    pub max_confidence_bps: u16,
+    pub payment_mint: Pubkey,       // pool payment mint when the round was opened, default for lamports
 }
 
 impl PresaleState {
-    pub const SPACE: usize = 8 * 3 + 2 * 2;
+    pub const SPACE: usize = 8 * 8 + 2 * 4 + 1 + 32 * 3 + 1 + 1;
+
+    pub fn is_usd_priced(&self) -> bool {
+        self.usd_price_per_node > 0
+    }
+
+    pub fn remaining_amount(&self) -> u16 {
+        self.total_presale_amount.saturating_sub(self.sold_amount)
+    }
+
+    pub fn is_soft_cap_reached(&self) -> bool {
+        self.raised_amount >= self.soft_cap
+    }
+
+    pub fn is_whitelisted(&self) -> bool {
+        self.merkle_root != [0; 32]
+    }
 }
 
 #[account]
 pub struct PoolState {
+    pub pool_id: u16,
+    pub mint_pool: Pubkey,          // pool whose `mint` PDA this pool sells, itself unless the mint is shared
     pub max_allocation: u16,
     pub total_nodes: u16,
     pub total_tokens: u64,
     pub tokens_per_node: u64,
-    pub reward_per_node: u8,
+    pub reward_per_node: u64,       // base units each node earns per day
     pub treasury_to_selling: f32,
     pub selling_mint: Pubkey,
     pub payment_mint: Pubkey,
     pub selling_vault: Pubkey,
+    pub acc_reward_per_node: u128,
+    pub last_update_ts: i64,
+    pub unstake_cooldown: i64,
+    pub unstake_refund_rate: u16,
+    pub node_collection: Pubkey,
+    pub emission_schedule: Pubkey,
+    pub reward_vault: Pubkey,
+    pub total_staked_nodes: u32,
+    pub reward_liabilities: u128,
+    pub vesting_cliff: i64,
+    pub vesting_duration: i64,
+    pub early_exit_penalty: u16,
+    pub current_round: u8,
+    pub referral_bps: u16,
+    pub max_supply: u64,
+    pub minted_to_treasury: u64,
+    pub minted_to_selling: u64,
+    pub minted_to_rewards: u64,
 }
 
 impl PoolState {
-    pub const SPACE: usize = 32 * 3 + 8 * 2 + 2 + 2 + 1 + 4;
+    pub const SPACE: usize = 32 * 7 + 16 * 2 + 8 * 11 + 4 + 2 * 6 + 1 + 4;
+
+    pub fn minted_supply(&self) -> Option<u64> {
+        self.minted_to_treasury
+            .checked_add(self.minted_to_selling)?
+            .checked_add(self.minted_to_rewards)
+    }
+}
+
+#[account]
+pub struct PresalePurchase {
+    pub round_id: u8,
+    pub nodes: u16,
+    pub paid_amount: u64,
+    pub is_staked: bool,            // nodes moved to the stake entry after the round succeeded
+    pub nft_nodes: u16,             // part of `nodes` minted as NFTs when they are staked
+}
+
+impl PresalePurchase {
+    pub const SPACE: usize = 1 + 2 + 8 + 1 + 2;
 }
 
 #[account]
@@ -35,8 +147,159 @@ pub struct UserStakeEntry {
     pub claimable_amount: u64,
     pub staked_amount: u16,
     pub last_staked_at: i64,
+    pub reward_debt: u128,
+    pub unstaking_amount: u16,
+    pub unstake_requested_at: i64,
+    pub unstaking_refund: u64,      // tokens owed for `unstaking_amount`, priced when the nodes were unstaked
+    pub presale_nodes: u16,         // nodes bought across all presale rounds
+    pub wrapped_nodes: u16,         // nodes this wallet wrapped into NFTs that are not burned yet
 }
 
 impl UserStakeEntry {
-    pub const SPACE: usize = 8 + 2 + 8;
-}
\ No newline at end of file
+    pub const SPACE: usize = 8 + 2 + 8 + 16 + 2 + 8 + 8 + 2 + 2;
+
+    /// Nodes counted against `PoolState::max_allocation`.
+    pub fn allocated_nodes(&self) -> u16 {
+        self.staked_amount.saturating_add(self.wrapped_nodes)
+    }
+}
+
+#[account]
+pub struct NodeCollection {
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub minted_nodes: u32,
+}
+
+impl NodeCollection {
+    pub const MAX_NAME_LEN: usize = 20;
+    pub const MAX_SYMBOL_LEN: usize = 10;
+    pub const MAX_URI_LEN: usize = 200;
+    pub const SPACE: usize = 32 + (4 + Self::MAX_NAME_LEN) + (4 + Self::MAX_SYMBOL_LEN) + (4 + Self::MAX_URI_LEN) + 4;
+}
+
+#[account]
+pub struct NodeNft {
+    pub mint: Pubkey,
+    pub reward_debt: u128,
+    pub claimable_amount: u64,
+    pub wrapped_by: Pubkey,         // wallet whose allocation the node counts against until it is burned
+}
+
+impl NodeNft {
+    pub const SPACE: usize = 32 + 16 + 8 + 32;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct EmissionEpoch {
+    pub start_at: i64,
+    pub reward_per_node: u64,       // base units each node earns per day
+}
+
+impl EmissionEpoch {
+    pub const SPACE: usize = 8 + 8;
+}
+
+#[account]
+pub struct EmissionSchedule {
+    pub epochs: Vec<EmissionEpoch>,
+    pub halving_interval: i64,      // when non zero, the last epoch's rate halves every interval
+}
+
+impl EmissionSchedule {
+    pub const MAX_EPOCHS: usize = 16;
+    pub const SPACE: usize = 4 + EmissionEpoch::SPACE * Self::MAX_EPOCHS + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct VestingTranche {
+    pub amount: u64,
+    pub released_amount: u64,
+    pub start_at: i64,
+    pub cliff: i64,                 // pool settings when the tranche was locked, later changes don't apply
+    pub duration: i64,
+}
+
+impl VestingTranche {
+    pub const SPACE: usize = 8 * 5;
+}
+
+#[account]
+pub struct RewardVesting {
+    pub unlocked_amount: u64,       // fully vested tranches that are not released yet
+    pub tranches: Vec<VestingTranche>,
+}
+
+impl RewardVesting {
+    pub const MAX_TRANCHES: usize = 16;
+    pub const SPACE: usize = 8 + 4 + VestingTranche::SPACE * Self::MAX_TRANCHES;
+}
+
+#[account]
+pub struct ReferralAccount {
+    pub referrer: Pubkey,
+    pub pending_tokens: u64,
+    pub pending_lamports: u64,
+    pub locked_lamports: u64,       // presale commission waiting for `locked_round` to end
+    pub locked_round: u8,
+    pub referred_nodes: u32,
+}
+
+impl ReferralAccount {
+    pub const SPACE: usize = 32 + 8 * 3 + 1 + 4;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum ProposalAction {
+    WithdrawCap { round_id: u8 },
+    MintTokens { amount: u64 },
+    MintNodes { amount: u16 },
+    MintRewards { amount: u64 },
+    ChangeThreshold { threshold: u8 },
+    AddSigner { signer: Pubkey },
+    RemoveSigner { signer: Pubkey },
+}
+
+#[account]
+pub struct Multisig {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub proposal_count: u64,
+    pub signers_version: u32,       // bumped whenever `signers` changes
+}
+
+impl Multisig {
+    pub const MAX_SIGNERS: usize = 16;
+    pub const SPACE: usize = 4 + 32 * Self::MAX_SIGNERS + 1 + 8 + 4;
+}
+
+#[account]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub pool_state: Pubkey,         // pool the action applies to, ignored by multisig only actions
+    pub action: ProposalAction,
+    pub approvals: u16,             // bit per index in `Multisig::signers`
+    pub expires_at: i64,
+    pub is_executed: bool,
+    pub signers_version: u32,       // `Multisig::signers_version` the approvals were given for
+}
+
+impl Proposal {
+    pub const SPACE: usize = 8 + 32 * 2 + (1 + 32) + 2 + 8 + 1 + 4;
+}
+
+#[account]
+pub struct PendingPoolUpdate {
+    pub tokens_per_node: u64,
+    pub reward_per_node: u64,
+    pub max_allocation: u16,
+    pub treasury_to_selling: f32,
+    pub eta: i64,                   // earliest time the update can be executed
+}
+
+impl PendingPoolUpdate {
+    pub const SPACE: usize = 8 + 8 + 2 + 4 + 8;
+}
@@ -32,4 +32,134 @@ pub enum ErrorCode {
     UnavailableCaculateSum,
     #[msg("Can not calculate sub")]
     UnavailableCaculateSub,
+    #[msg("There are no rewards to claim")]
+    NothingToClaim,
+    #[msg("Vault token balance is not enough to pay rewards")]
+    LackVaultTokenBalance,
+    #[msg("Can not calculate rewards")]
+    UnableCalculatingRewards,
+    #[msg("Can not calculate basis points")]
+    UnableCalculatingBasisPoints,
+    #[msg("Basis points must not exceed 10000")]
+    InvalidBasisPoints,
+    #[msg("Unstake amount is invalid")]
+    InvalidUnstakeAmount,
+    #[msg("Unstake cooldown is not over")]
+    UnstakeCooldownActive,
+    #[msg("There are no unstaked nodes to withdraw")]
+    NothingToWithdraw,
+    #[msg("Metadata field is too long")]
+    InvalidMetadata,
+    #[msg("Node NFTs are not enabled for this pool")]
+    NodeNftDisabled,
+    #[msg("Signer doesn't hold this node NFT")]
+    InvalidNodeNftHolder,
+    #[msg("User doesn't have staked nodes")]
+    NoStakedNodes,
+    #[msg("Stake entry of the wallet that wrapped the node is missing")]
+    MissingWrapperStakeEntry,
+    #[msg("Node NFT accounts don't match the nodes being minted")]
+    InvalidNodeNftAccounts,
+    #[msg("Nodes transfer is invalid")]
+    InvalidTransferAmount,
+    #[msg("Emission schedule is invalid")]
+    InvalidEmissionSchedule,
+    #[msg("Emission schedule account is missing")]
+    MissingEmissionSchedule,
+    #[msg("Fund amount is invalid")]
+    InvalidFundAmount,
+    #[msg("Rewards are not enough to compound into a node")]
+    InsufficientRewardsToCompound,
+    #[msg("Vesting settings are invalid")]
+    InvalidVestingSettings,
+    #[msg("Can not calculate vested rewards")]
+    UnableCalculatingVesting,
+    #[msg("There are no vested rewards to release")]
+    NothingToRelease,
+    #[msg("Too many rewards are vesting at once")]
+    TooManyVestingTranches,
+    #[msg("Payment token account doesn't match the presale payment mint")]
+    InvalidPaymentMint,
+    #[msg("Token payment accounts are missing")]
+    MissingPaymentAccounts,
+    #[msg("Payment mint can't change once a presale round exists")]
+    PresaleAlreadyInitialized,
+    #[msg("Presale window is invalid")]
+    InvalidPresaleWindow,
+    #[msg("No more presale rounds can be opened")]
+    PresaleRoundOverflow,
+    #[msg("Wallet is not whitelisted for this allocation")]
+    InvalidWhitelistProof,
+    #[msg("Whitelist allocation is exceeded")]
+    WhitelistAllocationOverflow,
+    #[msg("Presale soft cap is not reached")]
+    SoftCapNotReached,
+    #[msg("Presale soft cap is reached, refunds are disabled")]
+    SoftCapReached,
+    #[msg("Presale funds are already withdrawn")]
+    AlreadyWithdrawn,
+    #[msg("There is nothing to refund")]
+    NothingToRefund,
+    #[msg("There are no presale nodes to stake")]
+    NothingToStake,
+    #[msg("Presale round is already finalized")]
+    AlreadyFinalized,
+    #[msg("Live presale can only be extended")]
+    PresaleAlreadyStarted,
+    #[msg("Users can't refer themselves")]
+    SelfReferral,
+    #[msg("Referral commission of a previous presale round has to be settled first")]
+    InvalidReferralRound,
+    #[msg("Referrals are only paid on rounds priced in lamports")]
+    ReferralNotSupported,
+    #[msg("Price feed is invalid")]
+    InvalidPriceFeed,
+    #[msg("Price feed is stale")]
+    StalePrice,
+    #[msg("Price confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Node price moved beyond the allowed slippage")]
+    SlippageExceeded,
+    #[msg("Signer is not the pending authority")]
+    InvalidPendingAuthority,
+    #[msg("Another authority handover is already pending")]
+    AuthorityAlreadyPending,
+    #[msg("Multisig settings are invalid")]
+    InvalidMultisigSettings,
+    #[msg("Signer is not part of the multisig")]
+    InvalidMultisigSigner,
+    #[msg("Proposal is already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Proposal is expired")]
+    ProposalExpired,
+    #[msg("Proposal doesn't have enough approvals")]
+    ProposalNotApproved,
+    #[msg("Proposal doesn't match this action")]
+    InvalidProposalAction,
+    #[msg("Signer already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Multisig signers changed since the proposal was created")]
+    StaleProposal,
+    #[msg("Pool update is invalid")]
+    InvalidPoolUpdate,
+    #[msg("Pool update delay is too short")]
+    PoolUpdateTooEarly,
+    #[msg("Pool update is still timelocked")]
+    PoolUpdateLocked,
+    #[msg("Another pool update is already queued")]
+    PoolUpdateAlreadyQueued,
+    #[msg("Instruction is paused")]
+    Paused,
+    #[msg("Signer is not the guardian")]
+    InvalidGuardian,
+    #[msg("Pause flags are unknown")]
+    InvalidPauseFlags,
+    #[msg("Minting exceeds the max supply")]
+    SupplyCapExceeded,
+    #[msg("Mint authority is already revoked")]
+    MintFinalized,
+    #[msg("Pool needs either its own mint or a shared pool")]
+    InvalidPoolMint,
+    #[msg("Pool owning the shared mint is missing")]
+    MissingMintPool,
 }
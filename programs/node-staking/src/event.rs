@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use crate::account::AuthorityRole;
+
+#[event]
+pub struct NodesTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u16,
+    pub transferred_at: i64,
+}
+
+#[event]
+pub struct RewardsFunded {
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+    pub reward_liabilities: u64,
+}
+
+#[event]
+pub struct PresaleFinalized {
+    pub round_id: u8,
+    pub sold_amount: u16,
+    pub unsold_amount: u16,
+    pub burned: bool,
+    pub pool_nodes: u16,
+    pub finalized_at: i64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub role: AuthorityRole,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct PoolUpdateQueued {
+    pub tokens_per_node: u64,
+    pub reward_per_node: u64,
+    pub max_allocation: u16,
+    pub treasury_to_selling: f32,
+    pub eta: i64,
+}
@@ -1,4 +1,13 @@
 use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+
+pub const BASIS_POINTS: u16 = 10_000;
+
+// limits of the token metadata program
+pub const MAX_TOKEN_NAME_LEN: usize = 32;
+pub const MAX_TOKEN_SYMBOL_LEN: usize = 10;
+pub const MAX_TOKEN_URI_LEN: usize = 200;
 
 pub fn send_lamports<'a>(from: AccountInfo<'a>, to: AccountInfo<'a>, amount: u64) -> Result<()> {
     let ix = anchor_lang::solana_program::system_instruction::transfer(
@@ -14,4 +23,103 @@ pub fn send_lamports<'a>(from: AccountInfo<'a>, to: AccountInfo<'a>, amount: u64
             to.to_account_info(),
         ]
     ).map_err(|err|err.into())
-}
\ No newline at end of file
+}
+
+pub fn apply_basis_points(amount: u64, basis_points: u16) -> Result<u64> {
+    let result = u128::from(amount)
+        .checked_mul(basis_points.into())
+        .ok_or(ErrorCode::UnableCalculatingBasisPoints)?
+        / u128::from(BASIS_POINTS);
+
+    u64::try_from(result).map_err(|_| ErrorCode::UnableCalculatingBasisPoints.into())
+}
+
+/// Part of `amount` that goes to the treasury when it is split
+/// `treasury_to_selling : 1` between the treasury and selling vaults.
+pub fn treasury_share(amount: u64, treasury_to_selling: f32) -> u64 {
+    let treasury_amount = (treasury_to_selling / (treasury_to_selling + 1.0)) * (amount as f32);
+    treasury_amount as u64
+}
+
+/// Pool keeping `max_supply` and the minted counters of `pool_state`'s mint:
+/// the pool itself, or `mint_pool` when it sells another pool's mint.
+pub fn supply_pool<'a>(pool_key: &Pubkey, pool_state: &'a mut PoolState, mint_pool: Option<&'a mut PoolState>) -> Result<&'a mut PoolState> {
+    if pool_state.mint_pool == *pool_key {
+        return Ok(pool_state);
+    }
+
+    mint_pool.ok_or(ErrorCode::MissingMintPool.into())
+}
+
+/// Rejects minting `amount` more tokens past the pool's `max_supply`.
+pub fn check_supply_cap(pool_state: &PoolState, amount: u64) -> Result<()> {
+    let minted_supply = pool_state.minted_supply()
+        .and_then(|minted| minted.checked_add(amount))
+        .ok_or(ErrorCode::SupplyCapExceeded)?;
+    require!(minted_supply <= pool_state.max_supply, ErrorCode::SupplyCapExceeded);
+
+    Ok(())
+}
+
+/// Rejects moving `amount` staked nodes from `sender` to itself, more nodes
+/// than it has staked, or past the recipient's `max_allocation`.
+pub fn check_node_transfer(
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    amount: u16,
+    sender_stake_entry: &UserStakeEntry,
+    recipient_stake_entry: &UserStakeEntry,
+    max_allocation: u16,
+) -> Result<()> {
+    require_keys_neq!(*sender, *recipient, ErrorCode::InvalidTransferAmount);
+    require!(amount > 0 && amount <= sender_stake_entry.staked_amount, ErrorCode::InvalidTransferAmount);
+
+    let recipient_nodes = recipient_stake_entry.allocated_nodes().checked_add(amount).ok_or(ErrorCode::UserAmountOverflow)?;
+    require!(recipient_nodes <= max_allocation, ErrorCode::UserAmountOverflow);
+
+    Ok(())
+}
+
+pub fn validate_token_metadata(name: &str, symbol: &str, uri: &str) -> Result<()> {
+    require!(!name.is_empty() && name.len() <= MAX_TOKEN_NAME_LEN, ErrorCode::InvalidMetadata);
+    require!(!symbol.is_empty() && symbol.len() <= MAX_TOKEN_SYMBOL_LEN, ErrorCode::InvalidMetadata);
+    require!(uri.len() <= MAX_TOKEN_URI_LEN, ErrorCode::InvalidMetadata);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(staked_amount: u16, wrapped_nodes: u16) -> UserStakeEntry {
+        let mut user_stake_entry = UserStakeEntry::deserialize(&mut &[0u8; UserStakeEntry::SPACE][..]).unwrap();
+        user_stake_entry.staked_amount = staked_amount;
+        user_stake_entry.wrapped_nodes = wrapped_nodes;
+        user_stake_entry
+    }
+
+    #[test]
+    fn check_node_transfer_rejects_self_and_unstaked_nodes() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let sender_stake_entry = entry(3, 0);
+        let recipient_stake_entry = entry(0, 0);
+
+        assert!(check_node_transfer(&sender, &recipient, 3, &sender_stake_entry, &recipient_stake_entry, 10).is_ok());
+        assert!(check_node_transfer(&sender, &sender, 1, &sender_stake_entry, &sender_stake_entry, 10).is_err());
+        assert!(check_node_transfer(&sender, &recipient, 0, &sender_stake_entry, &recipient_stake_entry, 10).is_err());
+        assert!(check_node_transfer(&sender, &recipient, 4, &sender_stake_entry, &recipient_stake_entry, 10).is_err());
+    }
+
+    #[test]
+    fn check_node_transfer_counts_wrapped_nodes_against_the_allocation() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let sender_stake_entry = entry(3, 0);
+        let recipient_stake_entry = entry(5, 3);
+
+        assert!(check_node_transfer(&sender, &recipient, 2, &sender_stake_entry, &recipient_stake_entry, 10).is_ok());
+        assert!(check_node_transfer(&sender, &recipient, 3, &sender_stake_entry, &recipient_stake_entry, 10).is_err());
+    }
+}
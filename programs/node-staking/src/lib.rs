@@ -2,86 +2,336 @@ mod account; use account::*;
 mod error; use error::ErrorCode;
 mod access_control; use access_control::*;
 mod helper; use helper::*;
+mod reward; use reward::*;
+mod event; use event::*;
+mod vesting; use vesting::*;
+mod merkle; use merkle::*;
+mod referral; use referral::*;
+mod oracle; use oracle::*;
+mod multisig; use multisig::*;
+mod nft; use nft::*;
 
 use {
     anchor_lang::prelude::*,
-    solana_program::{pubkey, pubkey::Pubkey},
+    solana_program::pubkey::Pubkey,
     anchor_spl::{
         associated_token::AssociatedToken,
-        token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer},
+        token::{mint_to, set_authority, spl_token::instruction::AuthorityType, transfer, Mint, MintTo, SetAuthority, Token, TokenAccount, Transfer},
         metadata::{
+            burn_nft,
+            create_master_edition_v3,
             create_metadata_accounts_v3,
-            mpl_token_metadata::types::DataV2,
+            update_metadata_accounts_v2,
+            mpl_token_metadata::types::{CollectionDetails, DataV2},
+            BurnNft,
+            CreateMasterEditionV3,
             CreateMetadataAccountsV3,
             Metadata as Metaplex,
+            UpdateMetadataAccountsV2,
         },
     }
 };
 
 declare_id!("6TMoqcXXGgtyEtzhz9KR1YpBNGw9Zj9MEi9vnASLzAvh");
 
-pub static POOL_AUTHORITY: Pubkey = pubkey!("EMZQyHyda9aXWqJsJYDUHCEbE5kibagRkNxY8TbPndYx");
+// staking opens once the first presale round is over; nodes of every round, this one included,
+// only earn after their round met its soft cap and they were staked with `stake_presale_nodes`
+pub const FIRST_PRESALE_ROUND: u8 = 0;
 
-pub static VAULT_AUTHORITY: Pubkey = pubkey!("6JvsMVc9rwY9AG63qsqrfoDcNPgRmx9JfMHMHaX7TRoS");
+// stakers get at least this long to react to a queued pool update
+pub const MIN_POOL_UPDATE_DELAY: i64 = 2 * 24 * 60 * 60;
 
 #[program]
 pub mod node_staking {
     use super::*;
 
+    pub fn initialize_config(ctx: Context<InitializeConfig>, vault_authority: Pubkey, operator: Pubkey, treasury: Pubkey, guardian: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.vault_authority = vault_authority;
+        config.operator = operator;
+        config.treasury = treasury;
+        config.pending_authority = Pubkey::default();
+        config.pending_role = AuthorityRole::Admin;
+        config.guardian = guardian;
+        config.paused = 0;
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: u8) -> Result<()> {
+        require!(paused & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+
+        let config = &mut ctx.accounts.config;
+        config.paused = paused;
+        Ok(())
+    }
+
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, role: AuthorityRole, new_authority: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        // a pending handover has to be cancelled before proposing another one
+        require!(config.pending_authority == Pubkey::default(), ErrorCode::AuthorityAlreadyPending);
+
+        config.pending_authority = new_authority;
+        config.pending_role = role;
+        Ok(())
+    }
+
+    pub fn cancel_authority(ctx: Context<CancelAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.pending_authority = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let new_authority = ctx.accounts.pending_authority.key();
+        let role = config.pending_role;
+
+        let authority = match role {
+            AuthorityRole::Admin => &mut config.admin,
+            AuthorityRole::Vault => &mut config.vault_authority,
+            AuthorityRole::Operator => &mut config.operator,
+            AuthorityRole::Treasury => &mut config.treasury,
+            AuthorityRole::Guardian => &mut config.guardian,
+        };
+        let previous_authority = *authority;
+        *authority = new_authority;
+
+        config.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferred {
+            role,
+            previous_authority,
+            new_authority,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_multisig(ctx: Context<InitializeMultisig>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        validate_multisig(&signers, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.signers = signers;
+        multisig.threshold = threshold;
+        multisig.proposal_count = 0;
+        multisig.signers_version = 0;
+        Ok(())
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, pool_state: Pubkey, action: ProposalAction, expires_at: i64) -> Result<()> {
+        require!(expires_at > ctx.accounts.clock.unix_timestamp, ErrorCode::ProposalExpired);
+
+        let multisig = &mut ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.id = multisig.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.pool_state = pool_state;
+        proposal.action = action;
+        proposal.approvals = 0;
+        proposal.expires_at = expires_at;
+        proposal.is_executed = false;
+        proposal.signers_version = multisig.signers_version;
+
+        // proposing counts as the proposer's approval
+        approve(proposal, multisig, &ctx.accounts.proposer.key())?;
+
+        multisig.proposal_count = multisig.proposal_count.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+        Ok(())
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(!proposal.is_executed, ErrorCode::ProposalAlreadyExecuted);
+        require!(ctx.accounts.clock.unix_timestamp <= proposal.expires_at, ErrorCode::ProposalExpired);
+
+        approve(proposal, &ctx.accounts.multisig, &ctx.accounts.signer.key())
+    }
+
+    /// Executes proposals that only change multisig parameters, asset moving
+    /// proposals are consumed by the instruction they authorize.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let action = ctx.accounts.proposal.action.clone();
+        let pool_state = ctx.accounts.proposal.pool_state;
+
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &pool_state, &action, ctx.accounts.clock.unix_timestamp)?;
+
+        apply_multisig_action(&mut ctx.accounts.multisig, &action)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
+        pool_id: u16,
         tokens_per_node: u64,           // number of tokens to purchase node
-        reward_per_node: u8,            // reward of each node to user per day
+        reward_per_node: u64,           // base units each node earns per day
         max_allocation: u16,            // limit number of nodes purchased by each wallet
         treasury_to_selling: f32,
+        unstake_cooldown: i64,          // seconds between unstaking nodes and withdrawing the refund
+        unstake_refund_rate: u16,       // basis points of tokens_per_node refunded per unstaked node
+        max_supply: u64,                // most NST base units the program will ever mint, unused by pools sharing another pool's mint
     ) -> Result<()> {
+        require!(unstake_refund_rate <= BASIS_POINTS, ErrorCode::InvalidBasisPoints);
+
+        let (mint_pool, selling_mint) = match (&ctx.accounts.mint, &ctx.accounts.shared_pool) {
+            (Some(mint), None) => (ctx.accounts.pool_state.key(), mint.key()),
+            (None, Some(shared_pool)) => (shared_pool.mint_pool, shared_pool.selling_mint),
+            _ => return err!(ErrorCode::InvalidPoolMint),
+        };
+
         let pool_state = &mut ctx.accounts.pool_state;
 
+        pool_state.pool_id = pool_id;
+        pool_state.mint_pool = mint_pool;
+        pool_state.selling_mint = selling_mint;
         pool_state.total_nodes = 0;
         pool_state.tokens_per_node = tokens_per_node;
         pool_state.reward_per_node = reward_per_node;
         pool_state.max_allocation = max_allocation;
         pool_state.treasury_to_selling = treasury_to_selling;
+        pool_state.acc_reward_per_node = 0;
+        pool_state.unstake_cooldown = unstake_cooldown;
+        pool_state.unstake_refund_rate = unstake_refund_rate;
+        pool_state.max_supply = max_supply;
+
+        Ok(())
+    }
+
+    pub fn queue_pool_update(
+        ctx: Context<QueuePoolUpdate>,
+        tokens_per_node: u64,
+        reward_per_node: u64,
+        max_allocation: u16,
+        treasury_to_selling: f32,
+        eta: i64,
+    ) -> Result<()> {
+        let earliest_eta = ctx.accounts.clock.unix_timestamp.checked_add(MIN_POOL_UPDATE_DELAY).ok_or(ErrorCode::UnavailableCaculateSum)?;
+
+        require!(eta >= earliest_eta, ErrorCode::PoolUpdateTooEarly);
+        require!(tokens_per_node > 0 && max_allocation > 0, ErrorCode::InvalidPoolUpdate);
+        require!(treasury_to_selling.is_finite() && treasury_to_selling >= 0.0, ErrorCode::InvalidPoolUpdate);
+
+        // a queued update has to be executed or cancelled before queueing another one
+        let pending_pool_update = &mut ctx.accounts.pending_pool_update;
+        require!(pending_pool_update.eta == 0, ErrorCode::PoolUpdateAlreadyQueued);
+
+        pending_pool_update.tokens_per_node = tokens_per_node;
+        pending_pool_update.reward_per_node = reward_per_node;
+        pending_pool_update.max_allocation = max_allocation;
+        pending_pool_update.treasury_to_selling = treasury_to_selling;
+        pending_pool_update.eta = eta;
+
+        emit!(PoolUpdateQueued {
+            tokens_per_node,
+            reward_per_node,
+            max_allocation,
+            treasury_to_selling,
+            eta,
+        });
+
+        Ok(())
+    }
 
+    pub fn execute_pool_update(ctx: Context<ExecutePoolUpdate>) -> Result<()> {
+        apply_pool_update(
+            &mut ctx.accounts.pool_state,
+            &ctx.accounts.pending_pool_update,
+            ctx.accounts.emission_schedule.as_deref(),
+            ctx.accounts.clock.unix_timestamp,
+        )
+    }
+
+    pub fn cancel_pool_update(_ctx: Context<CancelPoolUpdate>) -> Result<()> {
         Ok(())
     }
 
     pub fn initialize_selling_vault(ctx: Context<InitializeSellingVault>) -> Result<()> {
         let pool_state = &mut ctx.accounts.pool_state;
-        pool_state.selling_mint = ctx.accounts.mint.key();
         pool_state.selling_vault = ctx.accounts.selling_vault.key();
         Ok(())
     }
 
     pub fn initialize_presale(
         ctx: Context<InitializePresale>,
-        price_per_node: u64,            // when presale, sol to purchase node
+        price_per_node: u64,            // when presale, lamports to purchase node, or payment mint base units once it is configured
         max_allocation: u16,            // when presale, limit number of nodes purchased by each wallet
         presale_start_at: i64,         
         presale_end_at: i64,
         total_presale_amount: u16,
+        soft_cap: u64,                  // minimum raised by the round, buyers are refunded when it is missed
     ) -> Result<()> {
+        require!(presale_start_at < presale_end_at, ErrorCode::InvalidPresaleWindow);
+
         let presale = &mut ctx.accounts.presale;
         presale.price_per_node = price_per_node;
         presale.max_allocation = max_allocation;
         presale.presale_start_at = presale_start_at;
         presale.presale_end_at = presale_end_at;
         presale.total_presale_amount = total_presale_amount;
+        presale.round_id = FIRST_PRESALE_ROUND;
+        presale.sold_amount = 0;
+        presale.soft_cap = soft_cap;
+        presale.raised_amount = 0;
+        presale.is_withdrawn = false;
+        presale.payment_mint = ctx.accounts.pool_state.payment_mint;
         
+        // the presale supply is set aside from the pool inventory until the round is finalized
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.total_nodes = pool_state.total_nodes.checked_sub(total_presale_amount).ok_or(ErrorCode::LackNodes)?;
+        pool_state.current_round = FIRST_PRESALE_ROUND;
+
+        Ok(())
+    }
+
+    pub fn open_presale_round(
+        ctx: Context<OpenPresaleRound>,
+        price_per_node: u64,
+        max_allocation: u16,
+        presale_start_at: i64,         
+        presale_end_at: i64,
+        total_presale_amount: u16,
+        soft_cap: u64,                  // minimum raised by the round, buyers are refunded when it is missed
+    ) -> Result<()> {
+        let previous_presale = &ctx.accounts.previous_presale;
+
+        require!(ctx.accounts.clock.unix_timestamp > previous_presale.presale_end_at, ErrorCode::NotEndedPresale);
+        require!(presale_start_at >= previous_presale.presale_end_at, ErrorCode::InvalidPresaleWindow);
+        require!(presale_start_at < presale_end_at, ErrorCode::InvalidPresaleWindow);
+
+        let round_id = ctx.accounts.pool_state.current_round.checked_add(1).ok_or(ErrorCode::PresaleRoundOverflow)?;
+
+        let presale = &mut ctx.accounts.presale;
+        presale.price_per_node = price_per_node;
+        presale.max_allocation = max_allocation;
+        presale.presale_start_at = presale_start_at;
+        presale.presale_end_at = presale_end_at;
+        presale.total_presale_amount = total_presale_amount;
+        presale.round_id = round_id;
+        presale.sold_amount = 0;
+        presale.soft_cap = soft_cap;
+        presale.raised_amount = 0;
+        presale.is_withdrawn = false;
+        presale.payment_mint = ctx.accounts.pool_state.payment_mint;
+
         let pool_state = &mut ctx.accounts.pool_state;
-        pool_state.total_nodes = total_presale_amount;
+        pool_state.total_nodes = pool_state.total_nodes.checked_sub(total_presale_amount).ok_or(ErrorCode::LackNodes)?;
+        pool_state.current_round = round_id;
 
         Ok(())
     }
 
-    pub fn initialize_token (ctx: Context<InitializeToken>) -> Result<()> {
-        let seeds = &["mint".as_bytes(), &[ctx.bumps.mint]];
+    pub fn initialize_token (ctx: Context<InitializeToken>, name: String, symbol: String, uri: String) -> Result<()> {
+        validate_token_metadata(&name, &symbol, &uri)?;
+
+        let mint_pool = ctx.accounts.pool_state.mint_pool;
+        let seeds = &["mint".as_bytes(), mint_pool.as_ref(), &[ctx.bumps.mint]];
         let signer = [&seeds[..]];
 
         let token_data: DataV2 = DataV2 {
-            name: "Solana Node Staking Token".to_string(),
-            symbol: "NST".to_string(),
-            uri: "https://ipfs.io/ipfs/QmRtzvCek4tv3u9r1zjEm3wZbuT8MQtaaoAGonZK1CATkB".to_string(),
+            name,
+            symbol,
+            uri,
             seller_fee_basis_points: 0,
             creators: None,
             collection: None,
@@ -113,14 +363,62 @@ pub mod node_staking {
         Ok(())
     }
 
+    pub fn update_token_metadata(
+        ctx: Context<UpdateTokenMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+        lock_metadata: bool,            // makes the metadata immutable, it can't be updated again
+    ) -> Result<()> {
+        validate_token_metadata(&name, &symbol, &uri)?;
+
+        let mint_pool = ctx.accounts.pool_state.mint_pool;
+        let seeds = &["mint".as_bytes(), mint_pool.as_ref(), &[ctx.bumps.mint]];
+
+        let token_data: DataV2 = DataV2 {
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: None,
+            uses: None,
+        };
+
+        update_metadata_accounts_v2(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(), 
+                UpdateMetadataAccountsV2 {
+                    metadata: ctx.accounts.metadata.to_account_info(),
+                    update_authority: ctx.accounts.mint.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            None,
+            Some(token_data),
+            None,
+            lock_metadata.then_some(false),
+        )
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_MINTING))]
     pub fn mint_tokens(ctx: Context<MintTokens>, amount: u64) -> Result<()> {
-        let seeds = &["mint".as_bytes(), &[ctx.bumps.mint]];
-        let signer = [&seeds[..]];
-        let treasury_to_selling = ctx.accounts.pool_state.treasury_to_selling;
-        let treasury_amount = (treasury_to_selling / (treasury_to_selling + 1.0)) * (amount as f32);
-        let treasury_amount = treasury_amount as u64;
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &ctx.accounts.pool_state.key(), &ProposalAction::MintTokens { amount }, ctx.accounts.clock.unix_timestamp)?;
+
+        let treasury_amount = treasury_share(amount, ctx.accounts.pool_state.treasury_to_selling);
         let selling_amount = amount.checked_sub(treasury_amount).ok_or(ErrorCode::UnableCalculatingSellingTokens)?;
 
+        // pools sharing a mint are all capped by the pool that owns it
+        let pool_key = ctx.accounts.pool_state.key();
+        let supply_pool = supply_pool(&pool_key, &mut ctx.accounts.pool_state, ctx.accounts.mint_pool.as_deref_mut())?;
+        check_supply_cap(supply_pool, amount)?;
+        supply_pool.minted_to_treasury = supply_pool.minted_to_treasury.checked_add(treasury_amount).ok_or(ErrorCode::AmountOverflow)?;
+        supply_pool.minted_to_selling = supply_pool.minted_to_selling.checked_add(selling_amount).ok_or(ErrorCode::AmountOverflow)?;
+
+        let mint_pool = ctx.accounts.pool_state.mint_pool;
+        let seeds = &["mint".as_bytes(), mint_pool.as_ref(), &[ctx.bumps.mint]];
+        let signer = [&seeds[..]];
+
         mint_to(
             CpiContext::new_with_signer(
                 ctx.accounts.mint.to_account_info(), 
@@ -150,438 +448,3363 @@ pub mod node_staking {
         Ok(())
     }
 
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_MINTING))]
+    pub fn mint_reward_tokens(ctx: Context<MintRewardTokens>, amount: u64) -> Result<()> {
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &ctx.accounts.pool_state.key(), &ProposalAction::MintRewards { amount }, ctx.accounts.clock.unix_timestamp)?;
+        require!(amount > 0, ErrorCode::InvalidFundAmount);
+
+        let pool_key = ctx.accounts.pool_state.key();
+        let supply_pool = supply_pool(&pool_key, &mut ctx.accounts.pool_state, ctx.accounts.mint_pool.as_deref_mut())?;
+        check_supply_cap(supply_pool, amount)?;
+        supply_pool.minted_to_rewards = supply_pool.minted_to_rewards.checked_add(amount).ok_or(ErrorCode::AmountOverflow)?;
+
+        let mint_pool = ctx.accounts.pool_state.mint_pool;
+        let seeds = &["mint".as_bytes(), mint_pool.as_ref(), &[ctx.bumps.mint]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                MintTo {
+                    authority: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.reward_vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info()
+                }, 
+                &[&seeds[..]]
+            ), 
+            amount,
+        )?;
+
+        ctx.accounts.reward_vault.reload()?;
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+
+        emit!(RewardsFunded {
+            funder: ctx.accounts.pool_authority.key(),
+            amount,
+            vault_balance: ctx.accounts.reward_vault.amount,
+            reward_liabilities: reward_liabilities(pool_state),
+        });
+
+        Ok(())
+    }
+
+    /// Revokes the mint authority for good, no NST can be minted afterwards.
+    pub fn finalize_mint(ctx: Context<FinalizeMint>) -> Result<()> {
+        require!(ctx.accounts.mint.mint_authority.is_some(), ErrorCode::MintFinalized);
+
+        let mint_pool = ctx.accounts.pool_state.mint_pool;
+        let seeds = &["mint".as_bytes(), mint_pool.as_ref(), &[ctx.bumps.mint]];
+
+        set_authority(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                SetAuthority {
+                    current_authority: ctx.accounts.mint.to_account_info(),
+                    account_or_mint: ctx.accounts.mint.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            AuthorityType::MintTokens,
+            None,
+        )
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_MINTING))]
     pub fn mint_nodes(ctx: Context<MintNodes>, amount: u16) -> Result<()> {
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &ctx.accounts.pool_state.key(), &ProposalAction::MintNodes { amount }, ctx.accounts.clock.unix_timestamp)?;
+
         let pool_state = &mut ctx.accounts.pool_state;
         pool_state.total_nodes = pool_state.total_nodes.checked_add(amount).ok_or(ErrorCode::AmountOverflow)?;
 
         Ok(())
     }
 
+    pub fn initialize_reward_vault(ctx: Context<InitializeRewardVault>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.reward_vault = ctx.accounts.reward_vault.key();
+        Ok(())
+    }
+
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidFundAmount);
+
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.funder_token_account.to_account_info(),
+                    to: ctx.accounts.reward_vault.to_account_info(),
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                }
+            ),
+            amount
+        )?;
+
+        ctx.accounts.reward_vault.reload()?;
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+
+        emit!(RewardsFunded {
+            funder: ctx.accounts.pool_authority.key(),
+            amount,
+            vault_balance: ctx.accounts.reward_vault.amount,
+            reward_liabilities: reward_liabilities(pool_state),
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_emission_schedule(
+        ctx: Context<InitializeEmissionSchedule>,
+        epochs: Vec<EmissionEpoch>,
+        halving_interval: i64,
+    ) -> Result<()> {
+        validate_emission_schedule(&epochs, halving_interval)?;
+
+        // everything accrued so far is paid at the flat rate
+        let pool_state = &mut ctx.accounts.pool_state;
+        update_pool(pool_state, None, ctx.accounts.clock.unix_timestamp)?;
+        pool_state.emission_schedule = ctx.accounts.emission_schedule.key();
+
+        let emission_schedule = &mut ctx.accounts.emission_schedule;
+        emission_schedule.epochs = epochs;
+        emission_schedule.halving_interval = halving_interval;
+
+        Ok(())
+    }
+
     pub fn initialize_user_stake(ctx: Context<InitializeUserStake>) -> Result<()> {
         let user_stake_entry = &mut ctx.accounts.user_stake_entry;
         user_stake_entry.claimable_amount = 0;
         user_stake_entry.staked_amount = 0;
+        user_stake_entry.reward_debt = 0;
 
         Ok(())
     }
 
-    #[access_control(round_presale(&ctx.accounts.presale_state, &ctx.accounts.clock))]
-    pub fn sell_nodes_at_presale(ctx: Context<PresaleNodes>, amount: u16) -> Result<()> {
-        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
-        let presale_state = &mut ctx.accounts.presale_state;
-        let pool_state = &mut ctx.accounts.pool_state;
-        let user_lamports = **ctx.accounts.user.to_account_info().try_borrow_lamports()?;
-        let needed_lamports = presale_state.price_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+    #[access_control(round_presale(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_PRESALE))]
+    pub fn sell_nodes_at_presale(
+        ctx: Context<PresaleNodes>,
+        amount: u16,
+        allocation: u16,                // whitelisted allocation of the buyer, ignored for open rounds
+        proof: Vec<[u8; 32]>,           // merkle proof of the allocation, ignored for open rounds
+        expected_price_per_node: u64,   // node price quoted to the buyer
+        max_slippage_bps: u16,          // how far above the quote the buyer still accepts to pay
+        as_nfts: bool,                  // nodes are minted as NFTs once they are staked
+    ) -> Result<()> {
+        let price_per_node = ctx.accounts.node_price()?;
+        let max_price_per_node = expected_price_per_node
+            .checked_add(apply_basis_points(expected_price_per_node, max_slippage_bps)?)
+            .ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        require!(price_per_node <= max_price_per_node, ErrorCode::SlippageExceeded);
+
+        let needed_payment = price_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+
+        let purchased_nodes = ctx.accounts.presale_purchase.nodes.checked_add(amount).ok_or(ErrorCode::StakesAmountOverflow)?;
+        let sold_nodes = ctx.accounts.presale_state.sold_amount.checked_add(amount).ok_or(ErrorCode::LackNodes)?;
 
-        require!(user_stake_entry.staked_amount + amount < presale_state.max_allocation, 
+        require!(purchased_nodes <= ctx.accounts.presale_state.max_allocation, 
             ErrorCode::StakesAmountOverflow
         );
-        require!(pool_state.total_nodes >= amount.into(), 
+        require!(sold_nodes <= ctx.accounts.presale_state.total_presale_amount, 
             ErrorCode::LackNodes
         );
-        require!(user_lamports > needed_lamports, ErrorCode::InsufficientBalanceForPresale);
 
-        let user = &mut ctx.accounts.user;
-        let presale_valut = &mut ctx.accounts.presale_vault;
+        if ctx.accounts.presale_state.is_whitelisted() {
+            let leaf = allocation_leaf(&ctx.accounts.user.key(), allocation);
+            require!(verify_proof(&proof, &ctx.accounts.presale_state.merkle_root, leaf), ErrorCode::InvalidWhitelistProof);
+            require!(purchased_nodes <= allocation, ErrorCode::WhitelistAllocationOverflow);
+        }
+
+        // price_per_node is in lamports unless the round was opened with a payment mint
+        if ctx.accounts.presale_state.payment_mint == Pubkey::default() {
+            ctx.accounts.pay_with_lamports(needed_payment)?;
+        } else {
+            ctx.accounts.pay_with_tokens(needed_payment)?;
+        }
+
+        let presale_purchase = &mut ctx.accounts.presale_purchase;
+        presale_purchase.round_id = ctx.accounts.presale_state.round_id;
+        presale_purchase.nodes = purchased_nodes;
+        if as_nfts {
+            presale_purchase.nft_nodes = presale_purchase.nft_nodes.checked_add(amount).ok_or(ErrorCode::StakesAmountOverflow)?;
+        }
+        presale_purchase.paid_amount = presale_purchase.paid_amount.checked_add(needed_payment).ok_or(ErrorCode::AmountOverflow)?;
+
+        let presale_state = &mut ctx.accounts.presale_state;
+        presale_state.sold_amount = sold_nodes;
+        presale_state.raised_amount = presale_state.raised_amount.checked_add(needed_payment).ok_or(ErrorCode::AmountOverflow)?;
 
-        send_lamports(user.to_account_info(), presale_valut.to_account_info(), needed_lamports)?;
+        if let Some(referral_account) = ctx.accounts.referral_account.as_deref_mut() {
+            // commission is paid in lamports, so token-paid rounds don't take referrals
+            require!(presale_state.payment_mint == Pubkey::default(), ErrorCode::ReferralNotSupported);
 
-        user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_add(amount).ok_or(ErrorCode::UserAmountOverflow)?;
+            if referral_account.locked_round != presale_state.round_id {
+                if let Some(referral_presale) = ctx.accounts.referral_presale.as_deref() {
+                    settle_presale_commission(referral_account, referral_presale, ctx.accounts.clock.unix_timestamp)?;
+                }
+            }
 
-        user_stake_entry.last_staked_at = presale_state.presale_end_at;
+            let commission = apply_basis_points(needed_payment, ctx.accounts.pool_state.referral_bps)?;
+            lock_presale_commission(referral_account, presale_state.round_id, commission)?;
+            presale_state.referral_commission = presale_state.referral_commission.checked_add(commission).ok_or(ErrorCode::AmountOverflow)?;
 
-        pool_state.total_nodes = pool_state.total_nodes.checked_sub(amount.into()).ok_or(ErrorCode::LackNodes)?;
+            referral_account.referred_nodes = referral_account.referred_nodes.checked_add(amount.into()).ok_or(ErrorCode::AmountOverflow)?;
+        }
 
         Ok(())
     }
 
-    pub fn withdraw_cap(ctx: Context<WithdrawCap>) -> Result<()> {
-        ctx.accounts.send_lamports_from_vault_to_owner()
-    }
+    /// Moves the nodes bought in a successful round into the buyer's stake
+    /// entry, they only start earning from here. Nodes bought as NFTs are
+    /// minted now rather than at purchase, so a failed round has nothing to
+    /// burn, with their accounts in `remaining_accounts`.
+    #[access_control(presale_succeeded(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_CREATE_NODES))]
+    pub fn stake_presale_nodes<'info>(ctx: Context<'_, '_, 'info, 'info, StakePresaleNodes<'info>>, _round_id: u8) -> Result<()> {
+        let presale_purchase = &mut ctx.accounts.presale_purchase;
+        let amount = presale_purchase.nodes;
+        let nft_nodes = presale_purchase.nft_nodes;
 
-    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock))]
-    pub fn create_nodes(ctx: Context<CreateNodes>, amount: u16) -> Result<()> {
-        let pool_nodes_amount = ctx.accounts.pool_state.total_nodes;
-        let tokens_per_node = ctx.accounts.pool_state.tokens_per_node;
-        let needed_tokens = tokens_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
-        let user_token_balance = ctx.accounts.user_token_account.amount;
+        require!(!presale_purchase.is_staked && amount > 0, ErrorCode::NothingToStake);
 
-        require!(pool_nodes_amount >= amount, ErrorCode::LackNodes);
-        require!(user_token_balance >= needed_tokens, ErrorCode::LackUserTokenBalance);
-        require!(amount <= ctx.accounts.pool_state.max_allocation, ErrorCode::UserAmountOverflow);
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+        let pool_state = &mut ctx.accounts.pool_state;
 
-        let treasury_to_selling = ctx.accounts.pool_state.treasury_to_selling;
-        let treasury_amount = (treasury_to_selling / (treasury_to_selling + 1.0)) * (needed_tokens as f32);
-        let treasury_amount = treasury_amount as u64;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+        let staked_nodes = amount.checked_sub(nft_nodes).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_add(staked_nodes).ok_or(ErrorCode::UserAmountOverflow)?;
+        user_stake_entry.wrapped_nodes = user_stake_entry.wrapped_nodes.checked_add(nft_nodes).ok_or(ErrorCode::UserAmountOverflow)?;
+        require!(user_stake_entry.allocated_nodes() <= pool_state.max_allocation, ErrorCode::UserAmountOverflow);
+        sync_reward_debt(user_stake_entry, pool_state)?;
+        pool_state.total_staked_nodes = pool_state.total_staked_nodes.checked_add(amount.into()).ok_or(ErrorCode::AmountOverflow)?;
 
-        let seeds = &[
-            "pool_state".as_bytes(),
-            &[ctx.bumps.pool_state]
-        ];
+        user_stake_entry.last_staked_at = ctx.accounts.clock.unix_timestamp;
+        user_stake_entry.presale_nodes = user_stake_entry.presale_nodes.checked_add(amount).ok_or(ErrorCode::UserAmountOverflow)?;
+        presale_purchase.is_staked = true;
 
-        transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(), 
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.selling_vault.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                }
-            ),
-            needed_tokens.into()
-        )?;
+        if nft_nodes > 0 {
+            ctx.accounts.mint_node_nfts(nft_nodes, ctx.remaining_accounts, ctx.bumps.pool_state)?;
+        }
 
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(), 
-                Transfer {
-                    from: ctx.accounts.selling_vault.to_account_info(),
-                    to: ctx.accounts.treasury_vault.to_account_info(),
-                    authority: ctx.accounts.pool_state.to_account_info(),
-                },
-                &[&seeds[..]]
-            ),
-            treasury_amount.into()
-        )?;
+        Ok(())
+    }
 
-        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
-        let user_nodes = user_stake_entry.staked_amount;
+    #[access_control(presale_succeeded(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_WITHDRAWALS))]
+    pub fn withdraw_cap(ctx: Context<WithdrawCap>, round_id: u8) -> Result<()> {
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &ctx.accounts.pool_state.key(), &ProposalAction::WithdrawCap { round_id }, ctx.accounts.clock.unix_timestamp)?;
 
-        msg!("token transfer successful");
+        require!(!ctx.accounts.presale_state.is_withdrawn, ErrorCode::AlreadyWithdrawn);
+        require!(ctx.accounts.presale_state.payment_mint == Pubkey::default(), ErrorCode::InvalidPaymentMint);
 
-        if user_nodes == 0 {
-            user_stake_entry.staked_amount = amount;
-            user_stake_entry.claimable_amount = 0;
-            user_stake_entry.last_staked_at = ctx.accounts.clock.unix_timestamp;
+        // referral commission stays in the vault until referrers claim it
+        let withdraw_amount = ctx.accounts.presale_state.raised_amount
+            .checked_sub(ctx.accounts.presale_state.referral_commission)
+            .ok_or(ErrorCode::UnavailableCaculateSub)?;
+        ctx.accounts.send_lamports_from_vault_to_owner(withdraw_amount)?;
+
+        let presale_state = &mut ctx.accounts.presale_state;
+        presale_state.is_withdrawn = true;
+
+        Ok(())
+    }
+
+    #[access_control(presale_failed(&ctx.accounts.presale_state, &ctx.accounts.clock))]
+    pub fn refund_presale(ctx: Context<RefundPresale>, _round_id: u8) -> Result<()> {
+        let refund_amount = ctx.accounts.presale_purchase.paid_amount;
+        let refunded_nodes = ctx.accounts.presale_purchase.nodes;
+
+        require!(refund_amount > 0 || refunded_nodes > 0, ErrorCode::NothingToRefund);
+
+        if ctx.accounts.presale_state.payment_mint == Pubkey::default() {
+            ctx.accounts.refund_lamports(refund_amount)?;
         } else {
-            let user_staked_amount = user_stake_entry.staked_amount;
-            let user_claimable_amount = user_stake_entry.claimable_amount;
-            let last_staked_period = ctx.accounts.clock.unix_timestamp - user_stake_entry.last_staked_at;
-            let additional_claim = user_staked_amount as f32 * 0.5 * ctx.accounts.selling_mint.decimals as f32 * last_staked_period as f32 / 86400 as f32;
-            let additional_claim = additional_claim as u64;
-            user_stake_entry.claimable_amount = user_claimable_amount.checked_add(additional_claim).ok_or(ErrorCode::UnavailableCaculateSum)?;
-            user_stake_entry.staked_amount = user_staked_amount.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
-            user_stake_entry.last_staked_at = ctx.accounts.clock.unix_timestamp;
+            ctx.accounts.refund_tokens(refund_amount, ctx.bumps.presale_vault)?;
         }
 
+        // nodes of a failed round were never staked, so they just go back to the pool
         let pool_state = &mut ctx.accounts.pool_state;
-        pool_state.total_nodes = pool_state.total_nodes.checked_sub(amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        pool_state.total_nodes = pool_state.total_nodes.checked_add(refunded_nodes).ok_or(ErrorCode::AmountOverflow)?;
+
+        let presale_purchase = &mut ctx.accounts.presale_purchase;
+        presale_purchase.nodes = 0;
+        presale_purchase.nft_nodes = 0;
+        presale_purchase.paid_amount = 0;
 
         Ok(())
     }
 
     #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock))]
-    pub fn claim_nodes(ctx: Context<ClaimNodes>, amount: u16) -> Result<()> {
+    pub fn finalize_presale(ctx: Context<FinalizePresale>, _round_id: u8, burn_unsold: bool) -> Result<()> {
+        require!(!ctx.accounts.presale_state.is_finalized, ErrorCode::AlreadyFinalized);
+
+        let presale_state = &mut ctx.accounts.presale_state;
+        let unsold_amount = presale_state.remaining_amount();
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        if !burn_unsold {
+            pool_state.total_nodes = pool_state.total_nodes.checked_add(unsold_amount).ok_or(ErrorCode::AmountOverflow)?;
+        }
+
+        presale_state.is_finalized = true;
+
+        emit!(PresaleFinalized {
+            round_id: presale_state.round_id,
+            sold_amount: presale_state.sold_amount,
+            unsold_amount,
+            burned: burn_unsold,
+            pool_nodes: pool_state.total_nodes,
+            finalized_at: ctx.accounts.clock.unix_timestamp,
+        });
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct InitializePresale<'info> {
-    #[account(
-        init, 
-        payer = pool_authority, 
-        space = 8 + PresaleState::SPACE,
-        seeds = [b"presale_state"],
-        bump
-    )]
-    pub presale: Account<'info, PresaleState>, 
-    #[account(
-        init,
-        payer = pool_authority,
-        space = 8,
-        seeds = [b"presale_vault"],
-        bump
-    )]
-    pub presale_valut: Account<'info, PresaleVault>,
-    #[account(
+    pub fn update_presale(
+        ctx: Context<UpdatePresale>,
+        price_per_node: u64,
+        max_allocation: u16,
+        presale_start_at: i64,
+        presale_end_at: i64,
+    ) -> Result<()> {
+        let now = ctx.accounts.clock.unix_timestamp;
+        let presale_state = &mut ctx.accounts.presale_state;
+
+        require!(presale_start_at < presale_end_at, ErrorCode::InvalidPresaleWindow);
+        require!(presale_state.presale_end_at >= now, ErrorCode::PresaleTooOld);
+
+        if presale_state.presale_start_at > now {
+            require!(presale_start_at >= now, ErrorCode::InvalidPresaleWindow);
+            // rounds don't overlap, a later one starts once the previous one ended
+            if presale_state.round_id != FIRST_PRESALE_ROUND {
+                let previous_presale = ctx.accounts.previous_presale.as_ref().ok_or(ErrorCode::InvalidPresaleWindow)?;
+                require!(presale_start_at >= previous_presale.presale_end_at, ErrorCode::InvalidPresaleWindow);
+            }
+
+            presale_state.price_per_node = price_per_node;
+            presale_state.max_allocation = max_allocation;
+            presale_state.presale_start_at = presale_start_at;
+        } else {
+            // buyers already paid at these terms, so a live round can only run longer
+            require!(
+                price_per_node == presale_state.price_per_node
+                    && max_allocation == presale_state.max_allocation
+                    && presale_start_at == presale_state.presale_start_at,
+                ErrorCode::PresaleAlreadyStarted
+            );
+            require!(presale_end_at >= presale_state.presale_end_at, ErrorCode::PresaleAlreadyStarted);
+        }
+
+        presale_state.presale_end_at = presale_end_at;
+
+        Ok(())
+    }
+
+    pub fn configure_usd_pricing(
+        ctx: Context<ConfigureUsdPricing>,
+        usd_price_per_node: u64,        // micro dollars per node, zero prices the round in lamports again
+        max_price_age: i64,             // seconds a price is accepted after it was published
+        max_confidence_bps: u16,        // widest accepted confidence interval in basis points of the price
+    ) -> Result<()> {
+        require!(max_confidence_bps <= BASIS_POINTS, ErrorCode::InvalidBasisPoints);
+        require!(max_price_age > 0, ErrorCode::InvalidPriceFeed);
+
+        let presale_state = &mut ctx.accounts.presale_state;
+        require!(presale_state.presale_start_at > ctx.accounts.clock.unix_timestamp, ErrorCode::PresaleAlreadyStarted);
+        // the oracle prices nodes in lamports, token-paid rounds keep their token price
+        require!(presale_state.payment_mint == Pubkey::default(), ErrorCode::InvalidPaymentMint);
+
+        presale_state.usd_price_per_node = usd_price_per_node;
+        presale_state.price_feed = ctx.accounts.price_feed.key();
+        presale_state.max_price_age = max_price_age;
+        presale_state.max_confidence_bps = max_confidence_bps;
+
+        Ok(())
+    }
+
+    pub fn set_presale_whitelist(ctx: Context<SetPresaleWhitelist>, merkle_root: [u8; 32]) -> Result<()> {
+        let presale_state = &mut ctx.accounts.presale_state;
+        presale_state.merkle_root = merkle_root;
+        Ok(())
+    }
+
+    /// Makes presale rounds of the pool charge in `payment_mint` instead of
+    /// lamports. The payment vault is created here for good, so the mint
+    /// can't be changed afterwards nor set back to lamports.
+    pub fn configure_payment_mint(ctx: Context<ConfigurePaymentMint>) -> Result<()> {
+        // rounds keep the mint they were opened with, so it is fixed before the first one
+        require!(ctx.accounts.first_presale.data_is_empty(), ErrorCode::PresaleAlreadyInitialized);
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.payment_mint = ctx.accounts.payment_mint.key();
+        Ok(())
+    }
+
+    #[access_control(presale_succeeded(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_WITHDRAWALS))]
+    pub fn withdraw_payment_tokens(ctx: Context<WithdrawPaymentTokens>, round_id: u8) -> Result<()> {
+        consume_proposal(&mut ctx.accounts.proposal, &ctx.accounts.multisig, &ctx.accounts.pool_state.key(), &ProposalAction::WithdrawCap { round_id }, ctx.accounts.clock.unix_timestamp)?;
+
+        require!(!ctx.accounts.presale_state.is_withdrawn, ErrorCode::AlreadyWithdrawn);
+        require!(ctx.accounts.presale_state.payment_mint != Pubkey::default(), ErrorCode::InvalidPaymentMint);
+
+        let pool_key = ctx.accounts.pool_state.key();
+        let seeds = &[
+            "presale_vault".as_bytes(),
+            pool_key.as_ref(),
+            &[ctx.bumps.presale_vault]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.payment_vault.to_account_info(),
+                    to: ctx.accounts.withdrawer_payment_account.to_account_info(),
+                    authority: ctx.accounts.presale_vault.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            ctx.accounts.presale_state.raised_amount
+        )?;
+
+        let presale_state = &mut ctx.accounts.presale_state;
+        presale_state.is_withdrawn = true;
+
+        Ok(())
+    }
+
+    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_CREATE_NODES))]
+    pub fn create_nodes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateNodes<'info>>,
+        amount: u16,
+        as_nfts: bool,                  // mints one NFT per node, with its accounts in `remaining_accounts`
+    ) -> Result<()> {
+        let pool_nodes_amount = ctx.accounts.pool_state.total_nodes;
+        let tokens_per_node = ctx.accounts.pool_state.tokens_per_node;
+        let needed_tokens = tokens_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        let user_token_balance = ctx.accounts.user_token_account.amount;
+
+        require!(pool_nodes_amount >= amount, ErrorCode::LackNodes);
+        require!(user_token_balance >= needed_tokens, ErrorCode::LackUserTokenBalance);
+
+        let treasury_amount = treasury_share(needed_tokens, ctx.accounts.pool_state.treasury_to_selling);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    to: ctx.accounts.selling_vault.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                }
+            ),
+            needed_tokens.into()
+        )?;
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.selling_vault.to_account_info(),
+                    to: ctx.accounts.treasury_vault.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            treasury_amount.into()
+        )?;
+
+        msg!("token transfer successful");
+
+        // commission stays in the selling vault until the referrer claims it
+        if let Some(referral_account) = ctx.accounts.referral_account.as_deref_mut() {
+            let commission = apply_basis_points(needed_tokens, ctx.accounts.pool_state.referral_bps)?;
+            referral_account.pending_tokens = referral_account.pending_tokens.checked_add(commission).ok_or(ErrorCode::UnavailableCaculateSum)?;
+            referral_account.referred_nodes = referral_account.referred_nodes.checked_add(amount.into()).ok_or(ErrorCode::AmountOverflow)?;
+        }
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+        if as_nfts {
+            // NFT nodes earn through their own state and count against the allocation like wrapped nodes
+            user_stake_entry.wrapped_nodes = user_stake_entry.wrapped_nodes.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        } else {
+            user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        }
+        require!(user_stake_entry.allocated_nodes() <= pool_state.max_allocation, ErrorCode::UserAmountOverflow);
+        user_stake_entry.last_staked_at = ctx.accounts.clock.unix_timestamp;
+        sync_reward_debt(user_stake_entry, pool_state)?;
+        pool_state.total_staked_nodes = pool_state.total_staked_nodes.checked_add(amount.into()).ok_or(ErrorCode::AmountOverflow)?;
+
+        pool_state.total_nodes = pool_state.total_nodes.checked_sub(amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+
+        if as_nfts {
+            ctx.accounts.mint_node_nfts(amount, ctx.remaining_accounts, ctx.bumps.pool_state)?;
+        }
+
+        Ok(())
+    }
+
+    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_CLAIMS))]
+    pub fn claim_nodes(ctx: Context<ClaimNodes>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+
+        require!(user_stake_entry.claimable_amount > 0, ErrorCode::NothingToClaim);
+
+        if is_vesting_enabled(pool_state) {
+            lock_rewards(&mut ctx.accounts.reward_vesting, pool_state, user_stake_entry.claimable_amount, ctx.accounts.clock.unix_timestamp)?;
+            user_stake_entry.claimable_amount = 0;
+            return Ok(());
+        }
+
+        // an underfunded vault pays what it holds and the rest stays claimable
+        let claim_amount = user_stake_entry.claimable_amount.min(ctx.accounts.reward_vault.amount);
+
+        require!(claim_amount > 0, ErrorCode::LackVaultTokenBalance);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            claim_amount
+        )?;
+
+        user_stake_entry.claimable_amount = user_stake_entry.claimable_amount.checked_sub(claim_amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        release_liabilities(&mut ctx.accounts.pool_state, claim_amount)?;
+
+        Ok(())
+    }
+
+    pub fn configure_referral(ctx: Context<ConfigureReferral>, referral_bps: u16) -> Result<()> {
+        require!(referral_bps <= BASIS_POINTS, ErrorCode::InvalidBasisPoints);
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.referral_bps = referral_bps;
+        Ok(())
+    }
+
+    pub fn initialize_referral(ctx: Context<InitializeReferral>) -> Result<()> {
+        let referral_account = &mut ctx.accounts.referral_account;
+        referral_account.referrer = ctx.accounts.referrer.key();
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_CLAIMS))]
+    pub fn claim_referral_rewards(ctx: Context<ClaimReferralRewards>) -> Result<()> {
+        if let Some(referral_presale) = ctx.accounts.referral_presale.as_deref() {
+            settle_presale_commission(&mut ctx.accounts.referral_account, referral_presale, ctx.accounts.clock.unix_timestamp)?;
+        }
+
+        let pending_lamports = ctx.accounts.referral_account.pending_lamports;
+        let pending_tokens = ctx.accounts.referral_account.pending_tokens;
+
+        require!(pending_lamports > 0 || pending_tokens > 0, ErrorCode::NothingToClaim);
+        require!(ctx.accounts.selling_vault.amount >= pending_tokens, ErrorCode::LackVaultTokenBalance);
+
+        if pending_lamports > 0 {
+            ctx.accounts.send_lamports_from_vault_to_referrer(pending_lamports)?;
+        }
+
+        if pending_tokens > 0 {
+            let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+            let seeds = &[
+                "pool_state".as_bytes(),
+                pool_id.as_ref(),
+                &[ctx.bumps.pool_state]
+            ];
+
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(), 
+                    Transfer {
+                        from: ctx.accounts.selling_vault.to_account_info(),
+                        to: ctx.accounts.referrer_token_account.to_account_info(),
+                        authority: ctx.accounts.pool_state.to_account_info(),
+                    },
+                    &[&seeds[..]]
+                ),
+                pending_tokens
+            )?;
+        }
+
+        let referral_account = &mut ctx.accounts.referral_account;
+        referral_account.pending_lamports = 0;
+        referral_account.pending_tokens = 0;
+
+        Ok(())
+    }
+
+    pub fn configure_vesting(
+        ctx: Context<ConfigureVesting>,
+        vesting_cliff: i64,             // seconds after a claim before anything unlocks
+        vesting_duration: i64,          // seconds after a claim until everything is unlocked
+        early_exit_penalty: u16,        // basis points of locked rewards forfeited to the treasury on early exit
+    ) -> Result<()> {
+        require!(vesting_cliff >= 0 && vesting_duration >= vesting_cliff, ErrorCode::InvalidVestingSettings);
+        require!(early_exit_penalty <= BASIS_POINTS, ErrorCode::InvalidBasisPoints);
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.vesting_cliff = vesting_cliff;
+        pool_state.vesting_duration = vesting_duration;
+        pool_state.early_exit_penalty = early_exit_penalty;
+
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_CLAIMS))]
+    pub fn release_vested(ctx: Context<ReleaseVested>) -> Result<()> {
+        let now = ctx.accounts.clock.unix_timestamp;
+        let releasable = releasable_amount(&ctx.accounts.reward_vesting, now)?;
+        let release_amount = releasable.min(ctx.accounts.reward_vault.amount);
+
+        require!(releasable > 0, ErrorCode::NothingToRelease);
+        require!(release_amount > 0, ErrorCode::LackVaultTokenBalance);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            release_amount
+        )?;
+
+        consume_releasable(&mut ctx.accounts.reward_vesting, release_amount, now)?;
+        release_liabilities(&mut ctx.accounts.pool_state, release_amount)?;
+
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_CLAIMS))]
+    pub fn exit_vesting(ctx: Context<ExitVesting>) -> Result<()> {
+        let now = ctx.accounts.clock.unix_timestamp;
+        let reward_vesting = &ctx.accounts.reward_vesting;
+        let releasable = releasable_amount(reward_vesting, now)?;
+        let locked = locked_amount(reward_vesting, now)?;
+        let penalty_amount = apply_basis_points(locked, ctx.accounts.pool_state.early_exit_penalty)?;
+        let payout_amount = releasable
+            .checked_add(locked - penalty_amount)
+            .ok_or(ErrorCode::UnableCalculatingVesting)?;
+        let total_amount = payout_amount.checked_add(penalty_amount).ok_or(ErrorCode::UnableCalculatingVesting)?;
+
+        require!(total_amount > 0, ErrorCode::NothingToRelease);
+        require!(ctx.accounts.reward_vault.amount >= total_amount, ErrorCode::LackVaultTokenBalance);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            payout_amount
+        )?;
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.treasury_vault.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            penalty_amount
+        )?;
+
+        let reward_vesting = &mut ctx.accounts.reward_vesting;
+        reward_vesting.unlocked_amount = 0;
+        reward_vesting.tranches.clear();
+
+        release_liabilities(&mut ctx.accounts.pool_state, total_amount)?;
+
+        Ok(())
+    }
+
+    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_CREATE_NODES))]
+    pub fn compound_rewards(ctx: Context<CompoundRewards>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+        let reward_vesting = &mut ctx.accounts.reward_vesting;
+        let now = ctx.accounts.clock.unix_timestamp;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), now)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+
+        // with vesting on, fresh rewards are locked like a claim and only the vested part can buy nodes
+        let vesting_enabled = is_vesting_enabled(pool_state);
+        if vesting_enabled && user_stake_entry.claimable_amount > 0 {
+            lock_rewards(reward_vesting, pool_state, user_stake_entry.claimable_amount, now)?;
+            user_stake_entry.claimable_amount = 0;
+        }
+
+        let spendable_amount = if vesting_enabled {
+            releasable_amount(reward_vesting, now)?
+        } else {
+            user_stake_entry.claimable_amount
+        };
+
+        let tokens_per_node = pool_state.tokens_per_node;
+        let affordable_nodes = spendable_amount.checked_div(tokens_per_node).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        let allowed_nodes = pool_state.max_allocation.saturating_sub(user_stake_entry.allocated_nodes());
+        let amount = affordable_nodes
+            .min(allowed_nodes.into())
+            .min(pool_state.total_nodes.into()) as u16;
+
+        require!(amount > 0, ErrorCode::InsufficientRewardsToCompound);
+
+        let needed_tokens = tokens_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        let treasury_amount = treasury_share(needed_tokens, pool_state.treasury_to_selling);
+        let selling_amount = needed_tokens.checked_sub(treasury_amount).ok_or(ErrorCode::UnableCalculatingSellingTokens)?;
+
+        require!(ctx.accounts.reward_vault.amount >= needed_tokens, ErrorCode::LackVaultTokenBalance);
+
+        if vesting_enabled {
+            consume_releasable(reward_vesting, needed_tokens, now)?;
+        } else {
+            user_stake_entry.claimable_amount = user_stake_entry.claimable_amount.checked_sub(needed_tokens).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        }
+        user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        user_stake_entry.last_staked_at = now;
+        sync_reward_debt(user_stake_entry, pool_state)?;
+
+        release_liabilities(pool_state, needed_tokens)?;
+        pool_state.total_staked_nodes = pool_state.total_staked_nodes.checked_add(amount.into()).ok_or(ErrorCode::AmountOverflow)?;
+        pool_state.total_nodes = pool_state.total_nodes.checked_sub(amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.selling_vault.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            selling_amount
+        )?;
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.treasury_vault.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            treasury_amount
+        )?;
+
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_STAKE_CHANGES))]
+    pub fn transfer_nodes(ctx: Context<TransferNodes>, amount: u16) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let sender_stake_entry = &mut ctx.accounts.sender_stake_entry;
+        let recipient_stake_entry = &mut ctx.accounts.recipient_stake_entry;
+        let now = ctx.accounts.clock.unix_timestamp;
+
+        check_node_transfer(
+            &ctx.accounts.sender.key(),
+            &ctx.accounts.recipient.key(),
+            amount,
+            sender_stake_entry,
+            recipient_stake_entry,
+            pool_state.max_allocation,
+        )?;
+
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), now)?;
+        settle_rewards(sender_stake_entry, pool_state)?;
+        settle_rewards(recipient_stake_entry, pool_state)?;
+
+        sender_stake_entry.staked_amount = sender_stake_entry.staked_amount.checked_sub(amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        recipient_stake_entry.staked_amount = recipient_stake_entry.staked_amount.checked_add(amount).ok_or(ErrorCode::UserAmountOverflow)?;
+        recipient_stake_entry.last_staked_at = now;
+
+        sync_reward_debt(sender_stake_entry, pool_state)?;
+        sync_reward_debt(recipient_stake_entry, pool_state)?;
+
+        emit!(NodesTransferred {
+            from: ctx.accounts.sender.key(),
+            to: ctx.accounts.recipient.key(),
+            amount,
+            transferred_at: now,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_node_collection(
+        ctx: Context<InitializeNodeCollection>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        require!(name.len() <= NodeCollection::MAX_NAME_LEN, ErrorCode::InvalidMetadata);
+        require!(symbol.len() <= NodeCollection::MAX_SYMBOL_LEN, ErrorCode::InvalidMetadata);
+        require!(uri.len() <= NodeCollection::MAX_URI_LEN, ErrorCode::InvalidMetadata);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+        let signer = [&seeds[..]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                MintTo {
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                    to: ctx.accounts.collection_token_account.to_account_info(),
+                    mint: ctx.accounts.collection_mint.to_account_info()
+                }, 
+                &signer
+            ), 
+            1,
+        )?;
+
+        let collection_data: DataV2 = DataV2 {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: None,
+            uses: None,
+        };
+
+        create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(), 
+                CreateMetadataAccountsV3 {
+                    payer: ctx.accounts.pool_authority.to_account_info(),
+                    update_authority: ctx.accounts.pool_state.to_account_info(),
+                    mint: ctx.accounts.collection_mint.to_account_info(),
+                    metadata: ctx.accounts.collection_metadata.to_account_info(),
+                    mint_authority: ctx.accounts.pool_state.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                }, 
+                &signer
+            ),
+            collection_data,
+            true,
+            true,
+            Some(CollectionDetails::V1 { size: 0 }),
+        )?;
+
+        create_master_edition_v3(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_metadata_program.to_account_info(), 
+                CreateMasterEditionV3 {
+                    edition: ctx.accounts.collection_master_edition.to_account_info(),
+                    mint: ctx.accounts.collection_mint.to_account_info(),
+                    update_authority: ctx.accounts.pool_state.to_account_info(),
+                    mint_authority: ctx.accounts.pool_state.to_account_info(),
+                    payer: ctx.accounts.pool_authority.to_account_info(),
+                    metadata: ctx.accounts.collection_metadata.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    rent: ctx.accounts.rent.to_account_info(),
+                }, 
+                &signer
+            ),
+            Some(0),
+        )?;
+
+        let node_collection = &mut ctx.accounts.node_collection;
+        node_collection.mint = ctx.accounts.collection_mint.key();
+        node_collection.name = name;
+        node_collection.symbol = symbol;
+        node_collection.uri = uri;
+        node_collection.minted_nodes = 0;
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.node_collection = ctx.accounts.collection_mint.key();
+
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_NODE_NFTS))]
+    pub fn mint_node_nft(ctx: Context<MintNodeNft>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+
+        require!(user_stake_entry.staked_amount > 0, ErrorCode::NoStakedNodes);
+
+        // the node leaves the stake entry and earns through its NFT from now on
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+        user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_sub(1).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        user_stake_entry.wrapped_nodes = user_stake_entry.wrapped_nodes.checked_add(1).ok_or(ErrorCode::UserAmountOverflow)?;
+        sync_reward_debt(user_stake_entry, pool_state)?;
+
+        let node_nft = &mut ctx.accounts.node_nft;
+        node_nft.mint = ctx.accounts.node_mint.key();
+        node_nft.reward_debt = accumulated_rewards(1, pool_state)?;
+        node_nft.wrapped_by = ctx.accounts.user.key();
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        let minter = NodeNftMinter {
+            pool_state: ctx.accounts.pool_state.to_account_info(),
+            collection_mint: ctx.accounts.collection_mint.to_account_info(),
+            collection_metadata: ctx.accounts.collection_metadata.to_account_info(),
+            collection_master_edition: ctx.accounts.collection_master_edition.to_account_info(),
+            owner: ctx.accounts.user.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+            token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+        };
+        minter.mint_nft(
+            &ctx.accounts.node_collection,
+            &ctx.accounts.node_mint.to_account_info(),
+            &ctx.accounts.user_nft_account.to_account_info(),
+            &ctx.accounts.node_metadata,
+            &ctx.accounts.node_master_edition,
+            &[&seeds[..]],
+        )?;
+
+        let node_collection = &mut ctx.accounts.node_collection;
+        node_collection.minted_nodes = node_collection.minted_nodes.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+
+        Ok(())
+    }
+
+    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_CLAIMS))]
+    pub fn claim_node_nft(ctx: Context<ClaimNodeNft>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        let node_nft = &mut ctx.accounts.node_nft;
+        settle_node_nft_rewards(node_nft, pool_state)?;
+
+        require!(node_nft.claimable_amount > 0, ErrorCode::NothingToClaim);
+
+        if is_vesting_enabled(pool_state) {
+            lock_rewards(&mut ctx.accounts.reward_vesting, pool_state, node_nft.claimable_amount, ctx.accounts.clock.unix_timestamp)?;
+            node_nft.claimable_amount = 0;
+            return Ok(());
+        }
+
+        let claim_amount = node_nft.claimable_amount.min(ctx.accounts.reward_vault.amount);
+
+        require!(claim_amount > 0, ErrorCode::LackVaultTokenBalance);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(), 
+                Transfer {
+                    from: ctx.accounts.reward_vault.to_account_info(),
+                    to: ctx.accounts.holder_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            claim_amount
+        )?;
+
+        node_nft.claimable_amount = node_nft.claimable_amount.checked_sub(claim_amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        release_liabilities(&mut ctx.accounts.pool_state, claim_amount)?;
+
+        Ok(())
+    }
+
+    /// Burns a node NFT and puts the node back into the holder's stake entry,
+    /// together with the rewards it earned and didn't pay out yet.
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_NODE_NFTS))]
+    pub fn burn_node_nft(ctx: Context<BurnNodeNft>) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let node_nft = &mut ctx.accounts.node_nft;
+        let holder_stake_entry = &mut ctx.accounts.holder_stake_entry;
+
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_node_nft_rewards(node_nft, pool_state)?;
+        settle_rewards(holder_stake_entry, pool_state)?;
+
+        // the node stops counting against the allocation of the wallet that wrapped it
+        if node_nft.wrapped_by == ctx.accounts.holder.key() {
+            holder_stake_entry.wrapped_nodes = holder_stake_entry.wrapped_nodes.checked_sub(1).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        } else {
+            let wrapper_stake_entry = ctx.accounts.wrapper_stake_entry.as_deref_mut().ok_or(ErrorCode::MissingWrapperStakeEntry)?;
+            wrapper_stake_entry.wrapped_nodes = wrapper_stake_entry.wrapped_nodes.saturating_sub(1);
+        }
+
+        holder_stake_entry.claimable_amount = holder_stake_entry.claimable_amount.checked_add(node_nft.claimable_amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        holder_stake_entry.staked_amount = holder_stake_entry.staked_amount.checked_add(1).ok_or(ErrorCode::UserAmountOverflow)?;
+        holder_stake_entry.last_staked_at = ctx.accounts.clock.unix_timestamp;
+        sync_reward_debt(holder_stake_entry, pool_state)?;
+
+        require!(holder_stake_entry.allocated_nodes() <= pool_state.max_allocation, ErrorCode::UserAmountOverflow);
+
+        let collection_metadata = ctx.accounts.collection_metadata.to_account_info();
+
+        burn_nft(
+            CpiContext::new(
+                ctx.accounts.token_metadata_program.to_account_info(), 
+                BurnNft {
+                    metadata: ctx.accounts.node_metadata.to_account_info(),
+                    owner: ctx.accounts.holder.to_account_info(),
+                    mint: ctx.accounts.node_mint.to_account_info(),
+                    token: ctx.accounts.holder_nft_account.to_account_info(),
+                    edition: ctx.accounts.node_master_edition.to_account_info(),
+                    spl_token: ctx.accounts.token_program.to_account_info(),
+                }
+            ).with_remaining_accounts(vec![collection_metadata.clone()]),
+            Some(collection_metadata.key()),
+        )
+    }
+
+    /// Stops `amount` nodes from earning and queues their refund, priced at the
+    /// current `tokens_per_node` and `unstake_refund_rate`. The cooldown runs
+    /// from the latest unstake, so unstaking more nodes restarts it for every
+    /// node still waiting to be withdrawn.
+    #[access_control(round_staking(&ctx.accounts.presale_state, &ctx.accounts.clock) not_paused(&ctx.accounts.config, PAUSE_STAKE_CHANGES))]
+    pub fn unstake_nodes(ctx: Context<UnstakeNodes>, amount: u16) -> Result<()> {
+        let pool_state = &mut ctx.accounts.pool_state;
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+
+        require!(amount > 0 && amount <= user_stake_entry.staked_amount, ErrorCode::InvalidUnstakeAmount);
+
+        update_pool(pool_state, ctx.accounts.emission_schedule.as_deref(), ctx.accounts.clock.unix_timestamp)?;
+        settle_rewards(user_stake_entry, pool_state)?;
+        user_stake_entry.staked_amount = user_stake_entry.staked_amount.checked_sub(amount).ok_or(ErrorCode::UnavailableCaculateSub)?;
+        sync_reward_debt(user_stake_entry, pool_state)?;
+        pool_state.total_staked_nodes = pool_state.total_staked_nodes.checked_sub(amount.into()).ok_or(ErrorCode::UnavailableCaculateSub)?;
+
+        let principal = pool_state.tokens_per_node.checked_mul(amount.into()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        let refund_amount = apply_basis_points(principal, pool_state.unstake_refund_rate)?;
+
+        user_stake_entry.unstaking_amount = user_stake_entry.unstaking_amount.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        user_stake_entry.unstaking_refund = user_stake_entry.unstaking_refund.checked_add(refund_amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+        user_stake_entry.unstake_requested_at = ctx.accounts.clock.unix_timestamp;
+
+        Ok(())
+    }
+
+    #[access_control(not_paused(&ctx.accounts.config, PAUSE_WITHDRAWALS))]
+    pub fn withdraw_unstaked(ctx: Context<WithdrawUnstaked>) -> Result<()> {
+        let unstaking_amount = ctx.accounts.user_stake_entry.unstaking_amount;
+        let unlocked_at = ctx.accounts.user_stake_entry.unstake_requested_at
+            .checked_add(ctx.accounts.pool_state.unstake_cooldown)
+            .ok_or(ErrorCode::UnavailableCaculateSum)?;
+
+        require!(unstaking_amount > 0, ErrorCode::NothingToWithdraw);
+        require!(ctx.accounts.clock.unix_timestamp >= unlocked_at, ErrorCode::UnstakeCooldownActive);
+
+        let refund_amount = ctx.accounts.user_stake_entry.unstaking_refund;
+
+        require!(ctx.accounts.selling_vault.amount >= refund_amount, ErrorCode::LackVaultTokenBalance);
+
+        let pool_id = ctx.accounts.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[ctx.bumps.pool_state]
+        ];
+
+        if refund_amount > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(), 
+                    Transfer {
+                        from: ctx.accounts.selling_vault.to_account_info(),
+                        to: ctx.accounts.user_token_account.to_account_info(),
+                        authority: ctx.accounts.pool_state.to_account_info(),
+                    },
+                    &[&seeds[..]]
+                ),
+                refund_amount
+            )?;
+        }
+
+        let user_stake_entry = &mut ctx.accounts.user_stake_entry;
+        user_stake_entry.unstaking_amount = 0;
+        user_stake_entry.unstaking_refund = 0;
+
+        let pool_state = &mut ctx.accounts.pool_state;
+        pool_state.total_nodes = pool_state.total_nodes.checked_add(unstaking_amount).ok_or(ErrorCode::AmountOverflow)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::SPACE,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+    )]
+    pub program: Program<'info, crate::program::NodeStaking>,
+    // only the upgrade authority can bootstrap the config, so it can't be front-run after deploy
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        constraint = guardian.key() == config.guardian
+        @ ErrorCode::InvalidGuardian
+    )]
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        constraint = admin.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        constraint = admin.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        constraint = config.pending_authority != Pubkey::default()
+            && pending_authority.key() == config.pending_authority
+        @ ErrorCode::InvalidPendingAuthority
+    )]
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePresale<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init, 
+        payer = pool_authority, 
+        space = 8 + PresaleState::SPACE,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
+        bump
+    )]
+    pub presale: Account<'info, PresaleState>, 
+    #[account(
+        init,
+        payer = pool_authority,
+        space = 8,
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump
+    )]
+    pub presale_valut: Account<'info, PresaleVault>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPresaleRound<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round].as_ref()],
+        bump,
+    )]
+    pub previous_presale: Account<'info, PresaleState>,
+    #[account(
+        init, 
+        payer = pool_authority, 
+        space = 8 + PresaleState::SPACE,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round.wrapping_add(1)].as_ref()],
+        bump
+    )]
+    pub presale: Account<'info, PresaleState>, 
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[account]
+pub struct PresaleVault{
+
+}
+
+#[derive(Accounts)]
+pub struct InitializeMultisig<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = pool_authority,
+        space = 8 + Multisig::SPACE,
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::SPACE,
+        seeds = [b"proposal", multisig.proposal_count.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub signer: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(
+        mut,
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_id: u16)]
+pub struct InitializePool<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init, 
+        payer = pool_authority, 
+        space = 8 + PoolState::SPACE,
+        seeds = [b"pool_state", pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    // created when the pool sells its own token, omitted when `shared_pool` is passed
+    #[account(
+        init,
+        seeds = [b"mint", pool_state.key().as_ref()],
+        bump,
+        payer = pool_authority,
+        mint::decimals = 18,
+        mint::authority = mint,
+    )]
+    pub mint: Option<Account<'info, Mint>>,
+    #[account(
+        seeds = [b"pool_state", shared_pool.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub shared_pool: Option<Account<'info, PoolState>>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QueuePoolUpdate<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init_if_needed,
+        payer = pool_authority,
+        space = 8 + PendingPoolUpdate::SPACE,
+        seeds = [b"pending_pool_update", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub pending_pool_update: Account<'info, PendingPoolUpdate>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExecutePoolUpdate<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        close = pool_authority,
+        seeds = [b"pending_pool_update", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub pending_pool_update: Account<'info, PendingPoolUpdate>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPoolUpdate<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        close = pool_authority,
+        seeds = [b"pending_pool_update", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub pending_pool_update: Account<'info, PendingPoolUpdate>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSellingVault<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = pool_authority,
+        associated_token::mint = mint,
+        associated_token::authority = pool_state
+    )]
+    pub selling_vault: Account<'info, TokenAccount>,
+    #[account(
+        address = config.treasury,
+    )]
+    /// CHECK: Only the owner of the treasury token account
+    pub treasury: UncheckedAccount<'info>,
+    // pools sharing a mint also share the treasury account
+    #[account(
+        init_if_needed,
+        payer = pool_authority,
+        associated_token::mint = mint,
+        associated_token::authority = treasury
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct InitializeToken<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"mint", pool_state.mint_pool.as_ref()],
+        bump,
+        mint::authority = mint,
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub token_metadata_program: Program<'info, Metaplex>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTokenMetadata<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"mint", pool_state.mint_pool.as_ref()],
+        bump,
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_metadata_program: Program<'info, Metaplex>,
+}
+
+#[derive(Accounts)]
+pub struct MintTokens<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    // owner of the shared mint, only needed when `pool_state` sells another pool's mint
+    #[account(
+        mut,
+        address = pool_state.mint_pool,
+        constraint = mint_pool.key() != pool_state.key()
+        @ ErrorCode::InvalidPoolMint,
+    )]
+    pub mint_pool: Option<Account<'info, PoolState>>,
+    #[account(
+        mut,
+        seeds = [b"mint", pool_state.mint_pool.as_ref()],
+        bump,
+        mint::authority = mint
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config.treasury
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = pool_state
+    )]
+    pub selling_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct MintRewardTokens<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    // owner of the shared mint, only needed when `pool_state` sells another pool's mint
+    #[account(
+        mut,
+        address = pool_state.mint_pool,
+        constraint = mint_pool.key() != pool_state.key()
+        @ ErrorCode::InvalidPoolMint,
+    )]
+    pub mint_pool: Option<Account<'info, PoolState>>,
+    #[account(
+        mut,
+        seeds = [b"mint", pool_state.mint_pool.as_ref()],
+        bump,
+        mint::authority = mint
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeMint<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"mint", pool_state.mint_pool.as_ref()],
+        bump,
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MintNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewardVault<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = pool_authority,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = pool_state,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = funder_token_account.owner == pool_authority.key(),
+        constraint = funder_token_account.mint == selling_mint.key(),
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    #[account(
+        constraint = config.is_operator(&pool_authority.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeEmissionSchedule<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init,
+        payer = pool_authority,
+        space = 8 + EmissionSchedule::SPACE,
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Account<'info, EmissionSchedule>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserStake<'info> {
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStakeEntry::SPACE,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct PresaleNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub presale_vault: Account<'info, PresaleVault>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PresalePurchase::SPACE,
+        seeds = [b"presale_purchase", pool_state.key().as_ref(), [presale_state.round_id].as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub presale_purchase: Account<'info, PresalePurchase>,
+    #[account(
+        mut,
+        seeds = [b"payment_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub payment_vault: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = user_payment_account.owner == user.key(),
+        constraint = user_payment_account.mint == presale_state.payment_mint
+        @ ErrorCode::InvalidPaymentMint,
+    )]
+    pub user_payment_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"referral", pool_state.key().as_ref(), referral_account.referrer.as_ref()],
+        bump,
+        constraint = referral_account.referrer != user.key()
+        @ ErrorCode::SelfReferral,
+    )]
+    pub referral_account: Option<Account<'info, ReferralAccount>>,
+    // round of the commission still locked in `referral_account`, if it is not the current one
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [referral_presale.round_id].as_ref()],
+        bump,
+        constraint = referral_account.as_ref().is_some_and(|account| account.locked_round == referral_presale.round_id)
+        @ ErrorCode::InvalidReferralRound,
+    )]
+    pub referral_presale: Option<Account<'info, PresaleState>>,
+    #[account(
+        constraint = price_feed.key() == presale_state.price_feed
+        @ ErrorCode::InvalidPriceFeed,
+    )]
+    /// CHECK: The key is checked against the presale state and the data is parsed as a Pyth price account
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> PresaleNodes<'info> {
+    /// Price of a node in the payment currency, converted from dollars at
+    /// the oracle price for USD priced rounds paid in lamports.
+    pub fn node_price(&self) -> Result<u64> {
+        if !self.presale_state.is_usd_priced() || self.presale_state.payment_mint != Pubkey::default() {
+            return Ok(self.presale_state.price_per_node);
+        }
+
+        let Some(price_feed) = &self.price_feed else {
+            return err!(ErrorCode::InvalidPriceFeed);
+        };
+        let oracle_price = load_price(price_feed)?;
+        validate_price(&oracle_price, &self.presale_state, self.clock.unix_timestamp)?;
+
+        usd_to_lamports(self.presale_state.usd_price_per_node, &oracle_price)
+    }
+
+    pub fn pay_with_lamports(&self, amount: u64) -> Result<()> {
+        let user_lamports = **self.user.to_account_info().try_borrow_lamports()?;
+        require!(user_lamports > amount, ErrorCode::InsufficientBalanceForPresale);
+
+        send_lamports(self.user.to_account_info(), self.presale_vault.to_account_info(), amount)
+    }
+
+    pub fn pay_with_tokens(&self, amount: u64) -> Result<()> {
+        let (Some(payment_vault), Some(user_payment_account), Some(token_program)) =
+            (&self.payment_vault, &self.user_payment_account, &self.token_program)
+        else {
+            return err!(ErrorCode::MissingPaymentAccounts);
+        };
+        require!(user_payment_account.amount >= amount, ErrorCode::InsufficientBalanceForPresale);
+
+        transfer(
+            CpiContext::new(
+                token_program.to_account_info(), 
+                Transfer {
+                    from: user_payment_account.to_account_info(),
+                    to: payment_vault.to_account_info(),
+                    authority: self.user.to_account_info(),
+                }
+            ),
+            amount
+        )
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u8)]
+pub struct StakePresaleNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [round_id].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        mut,
+        seeds = [b"presale_purchase", pool_state.key().as_ref(), [round_id].as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub presale_purchase: Account<'info, PresalePurchase>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    // the accounts below are only needed to stake nodes bought as NFTs
+    #[account(
+        mut,
+        seeds = [b"node_collection_state", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub node_collection: Option<Box<Account<'info, NodeCollection>>>,
+    #[account(
+        mut,
+        address = pool_state.node_collection
+        @ ErrorCode::NodeNftDisabled
+    )]
+    pub collection_mint: Option<Box<Account<'info, Mint>>>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub collection_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub collection_master_edition: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub token_metadata_program: Option<Program<'info, Metaplex>>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> StakePresaleNodes<'info> {
+    pub fn mint_node_nfts(&mut self, amount: u16, remaining_accounts: &[AccountInfo<'info>], pool_state_bump: u8) -> Result<()> {
+        not_paused(&self.config, PAUSE_NODE_NFTS)?;
+
+        let minter = NodeNftMinter {
+            pool_state: self.pool_state.to_account_info(),
+            collection_mint: self.collection_mint.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            collection_metadata: self.collection_metadata.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            collection_master_edition: self.collection_master_edition.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            owner: self.user.to_account_info(),
+            rent: self.rent.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            token_metadata_program: self.token_metadata_program.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+        };
+        let node_collection = self.node_collection.as_deref_mut().ok_or(ErrorCode::NodeNftDisabled)?;
+        let reward_debt = accumulated_rewards(1, &self.pool_state)?;
+
+        let pool_id = self.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[pool_state_bump]
+        ];
+
+        minter.create_nfts(node_collection, amount, remaining_accounts, reward_debt, &[&seeds[..]])
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u8)]
+pub struct FinalizePresale<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [round_id].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        constraint = config.is_operator(&pool_authority.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePresale<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    // round before the current one, only needed to reschedule later rounds
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round.wrapping_sub(1)].as_ref()],
+        bump,
+    )]
+    pub previous_presale: Option<Account<'info, PresaleState>>,
+    #[account(
+        constraint = config.is_operator(&pool_authority.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureUsdPricing<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        owner = PYTH_PROGRAM_ID
+        @ ErrorCode::InvalidPriceFeed
+    )]
+    /// CHECK: Only the key is stored, the data is validated whenever a price is read
+    pub price_feed: UncheckedAccount<'info>,
+    #[account(
+        constraint = config.is_operator(&pool_authority.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct SetPresaleWhitelist<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [pool_state.current_round].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        constraint = config.is_operator(&pool_authority.key())
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigurePaymentMint<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    pub payment_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
+        bump,
+    )]
+    /// CHECK: Only checked to be uninitialized, no presale round may exist yet
+    pub first_presale: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    /// CHECK: Only the authority of the payment vault, it is created with the first presale round
+    pub presale_vault: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = pool_authority,
+        seeds = [b"payment_vault", pool_state.key().as_ref()],
+        bump,
+        token::mint = payment_mint,
+        token::authority = presale_vault,
+    )]
+    pub payment_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u8)]
+pub struct WithdrawPaymentTokens<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [round_id].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub presale_vault: Account<'info, PresaleVault>,
+    #[account(
+        mut,
+        seeds = [b"payment_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub payment_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = withdrawer_payment_account.owner == withdrawer.key(),
+        constraint = withdrawer_payment_account.mint == payment_vault.mint,
+    )]
+    pub withdrawer_payment_account: Account<'info, TokenAccount>,
+    #[account(
+        constraint = withdrawer.key() == config.vault_authority
+        @ ErrorCode::InvalidVaultAuthority
+    )]
+    pub withdrawer: Signer<'info>,
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u8)]
+pub struct WithdrawCap<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_state", pool_state.key().as_ref(), [round_id].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        mut,
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump
+    )]
+    pub presale_vault: Account<'info, PresaleVault>,
+    #[account(
+        mut,
+        constraint = withdrawer.key() == config.vault_authority
+        @ ErrorCode::InvalidVaultAuthority
+    )]
+    pub withdrawer: Signer<'info>,
+    #[account(
+        seeds = [b"multisig"],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> WithdrawCap<'info> {
+    pub fn send_lamports_from_vault_to_owner(&mut self, amount: u64) -> Result<()> {
+        let presale_vault = self.presale_vault.to_account_info();
+        let presale_vault_data_len = presale_vault.try_data_len()?;
+        let presale_vault_minimum_rent_exempt_balance = self.rent.minimum_balance(presale_vault_data_len);
+        let all_presale_vault_lamports = **presale_vault.try_borrow_lamports()?;
+        let available_lamports = all_presale_vault_lamports - presale_vault_minimum_rent_exempt_balance;
+
+        require!(available_lamports >= amount, ErrorCode::LackVaultTokenBalance);
+
+        **presale_vault.try_borrow_mut_lamports()? -= amount;
+        **self.withdrawer.try_borrow_mut_lamports()? += amount;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u8)]
+pub struct RefundPresale<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub presale_vault: Account<'info, PresaleVault>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [round_id].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        mut,
+        seeds = [b"presale_purchase", pool_state.key().as_ref(), [round_id].as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub presale_purchase: Account<'info, PresalePurchase>,
+    #[account(
+        mut,
+        seeds = [b"payment_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub payment_vault: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = user_payment_account.owner == user.key(),
+        constraint = user_payment_account.mint == presale_state.payment_mint
+        @ ErrorCode::InvalidPaymentMint,
+    )]
+    pub user_payment_account: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> RefundPresale<'info> {
+    pub fn refund_lamports(&self, amount: u64) -> Result<()> {
+        let presale_vault = self.presale_vault.to_account_info();
+
+        **presale_vault.try_borrow_mut_lamports()? = presale_vault.lamports().checked_sub(amount).ok_or(ErrorCode::LackVaultTokenBalance)?;
+        **self.user.try_borrow_mut_lamports()? += amount;
+
+        Ok(())
+    }
+
+    pub fn refund_tokens(&self, amount: u64, presale_vault_bump: u8) -> Result<()> {
+        let (Some(payment_vault), Some(user_payment_account), Some(token_program)) =
+            (&self.payment_vault, &self.user_payment_account, &self.token_program)
+        else {
+            return err!(ErrorCode::MissingPaymentAccounts);
+        };
+
+        let pool_key = self.pool_state.key();
+        let seeds = &[
+            "presale_vault".as_bytes(),
+            pool_key.as_ref(),
+            &[presale_vault_bump]
+        ];
+
+        transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(), 
+                Transfer {
+                    from: payment_vault.to_account_info(),
+                    to: user_payment_account.to_account_info(),
+                    authority: self.presale_vault.to_account_info(),
+                },
+                &[&seeds[..]]
+            ),
+            amount
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct CreateNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+        // has_one = selling_mint,
+        // has_one = selling_vault,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = config.treasury,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = pool_state,
+    )]
+    pub selling_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == selling_mint.key(),
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    #[account(
+        mut,
+        seeds = [b"referral", pool_state.key().as_ref(), referral_account.referrer.as_ref()],
+        bump,
+        constraint = referral_account.referrer != user.key()
+        @ ErrorCode::SelfReferral,
+    )]
+    pub referral_account: Option<Account<'info, ReferralAccount>>,
+    // the accounts below are only needed to buy nodes as NFTs
+    #[account(
+        mut,
+        seeds = [b"node_collection_state", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub node_collection: Option<Box<Account<'info, NodeCollection>>>,
+    #[account(
+        mut,
+        address = pool_state.node_collection
+        @ ErrorCode::NodeNftDisabled
+    )]
+    pub collection_mint: Option<Box<Account<'info, Mint>>>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub collection_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub collection_master_edition: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub token_metadata_program: Option<Program<'info, Metaplex>>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> CreateNodes<'info> {
+    pub fn mint_node_nfts(&mut self, amount: u16, remaining_accounts: &[AccountInfo<'info>], pool_state_bump: u8) -> Result<()> {
+        not_paused(&self.config, PAUSE_NODE_NFTS)?;
+
+        let minter = NodeNftMinter {
+            pool_state: self.pool_state.to_account_info(),
+            collection_mint: self.collection_mint.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            collection_metadata: self.collection_metadata.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            collection_master_edition: self.collection_master_edition.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            owner: self.user.to_account_info(),
+            rent: self.rent.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            associated_token_program: self.associated_token_program.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+            token_metadata_program: self.token_metadata_program.as_ref().ok_or(ErrorCode::NodeNftDisabled)?.to_account_info(),
+        };
+        let node_collection = self.node_collection.as_deref_mut().ok_or(ErrorCode::NodeNftDisabled)?;
+        let reward_debt = accumulated_rewards(1, &self.pool_state)?;
+
+        let pool_id = self.pool_state.pool_id.to_le_bytes();
+        let seeds = &[
+            "pool_state".as_bytes(),
+            pool_id.as_ref(),
+            &[pool_state_bump]
+        ];
+
+        minter.create_nfts(node_collection, amount, remaining_accounts, reward_debt, &[&seeds[..]])
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClaimNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
+        bump,
+    )]
+    pub presale_state: Account<'info, PresaleState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == selling_mint.key(),
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardVesting::SPACE,
+        seeds = [b"vesting", pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub reward_vesting: Account<'info, RewardVesting>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureReferral<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
+        @ ErrorCode::InvalidPoolAuthority
+    )]
+    pub pool_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeReferral<'info> {
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + ReferralAccount::SPACE,
+        seeds = [b"referral", pool_state.key().as_ref(), referrer.key().as_ref()],
+        bump,
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        mut,
+        seeds = [b"referral", pool_state.key().as_ref(), referrer.key().as_ref()],
+        bump,
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [referral_account.locked_round].as_ref()],
+        bump,
+    )]
+    pub referral_presale: Option<Account<'info, PresaleState>>,
+    #[account(
+        mut,
+        seeds = [b"presale_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub presale_vault: Account<'info, PresaleVault>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = pool_state,
+    )]
+    pub selling_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_token_account.owner == referrer.key(),
+        constraint = referrer_token_account.mint == selling_mint.key(),
+    )]
+    pub referrer_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl<'info> ClaimReferralRewards<'info> {
+    pub fn send_lamports_from_vault_to_referrer(&self, amount: u64) -> Result<()> {
+        let presale_vault = self.presale_vault.to_account_info();
+
+        **presale_vault.try_borrow_mut_lamports()? = presale_vault.lamports().checked_sub(amount).ok_or(ErrorCode::LackVaultTokenBalance)?;
+        **self.referrer.try_borrow_mut_lamports()? += amount;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ConfigureVesting<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
         mut,
-        constraint = pool_authority.key() == POOL_AUTHORITY
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        constraint = pool_authority.key() == config.admin
         @ ErrorCode::InvalidPoolAuthority
     )]
     pub pool_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseVested<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vesting", pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub reward_vesting: Account<'info, RewardVesting>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == selling_mint.key(),
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct ExitVesting<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = config.treasury,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vesting", pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub reward_vesting: Account<'info, RewardVesting>,
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == selling_mint.key(),
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct CompoundRewards<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Box<Account<'info, PoolState>>,
+    #[account(
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
+        bump,
+    )]
+    pub presale_state: Box<Account<'info, PresaleState>>,
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Box<Account<'info, Mint>>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub reward_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = config.treasury,
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = pool_state,
+    )]
+    pub selling_vault: Box<Account<'info, TokenAccount>>,
     #[account(
         mut,
-        seeds = [b"pool_state"],
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Box<Account<'info, UserStakeEntry>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardVesting::SPACE,
+        seeds = [b"vesting", pool_state.key().as_ref(), user.key().as_ref()],
         bump,
     )]
-    pub pool_state: Account<'info, PoolState>,
+    pub reward_vesting: Box<Account<'info, RewardVesting>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-}
-
-#[account]
-pub struct PresaleVault{
-
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct InitializePool<'info> {
+pub struct UnstakeNodes<'info> {
     #[account(
-        init, 
-        payer = pool_authority, 
-        space = 8 + PoolState::SPACE,
-        seeds = [b"pool_state"],
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
     )]
     pub pool_state: Account<'info, PoolState>,
     #[account(
-        init,
-        seeds = [b"mint"],
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
         bump,
-        payer = pool_authority,
-        mint::decimals = 18,
-        mint::authority = mint,
     )]
-    pub mint: Account<'info, Mint>,
+    pub presale_state: Account<'info, PresaleState>,
     #[account(
-        init,
-        payer = pool_authority,
-        associated_token::mint = mint,
-        associated_token::authority = pool_authority
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
     )]
-    pub treasury_vault: Account<'info, TokenAccount>,
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
     #[account(
         mut,
-        constraint = pool_authority.key() == POOL_AUTHORITY
-        @ ErrorCode::InvalidPoolAuthority
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
     )]
-    pub pool_authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    pub user: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeSellingVault<'info> {
+pub struct WithdrawUnstaked<'info> {
     #[account(
-        seeds = [b"pool_state"],
+        seeds = [b"config"],
         bump,
     )]
-    pub pool_state: Account<'info, PoolState>,
+    pub config: Account<'info, Config>,
     #[account(
-        seeds = [b"mint"],
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
     )]
-    pub mint: Account<'info, Mint>,
+    pub pool_state: Account<'info, PoolState>,
     #[account(
-        init,
-        payer = pool_authority,
-        associated_token::mint = mint,
-        associated_token::authority = pool_state
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = selling_mint,
+        associated_token::authority = pool_state,
     )]
     pub selling_vault: Account<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = pool_authority.key() == POOL_AUTHORITY
-        @ ErrorCode::InvalidPoolAuthority
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == selling_mint.key(),
     )]
-    pub pool_authority: Signer<'info>,
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    pub user: Signer<'info>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeToken<'info> {
-    #[account(mut)]
-    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
-    pub metadata: UncheckedAccount<'info>,
+pub struct InitializeNodeCollection<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [b"mint"],
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
-        mint::authority = mint,
     )]
-    pub mint: Account<'info, Mint>,
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        init,
+        payer = pool_authority,
+        space = 8 + NodeCollection::SPACE,
+        seeds = [b"node_collection_state", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub node_collection: Account<'info, NodeCollection>,
+    #[account(
+        init,
+        seeds = [b"node_collection", pool_state.key().as_ref()],
+        bump,
+        payer = pool_authority,
+        mint::decimals = 0,
+        mint::authority = pool_state,
+        mint::freeze_authority = pool_state,
+    )]
+    pub collection_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = pool_authority,
+        associated_token::mint = collection_mint,
+        associated_token::authority = pool_state
+    )]
+    pub collection_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub collection_metadata: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub collection_master_edition: UncheckedAccount<'info>,
     #[account(
         mut,
-        constraint = pool_authority.key() == POOL_AUTHORITY
+        constraint = pool_authority.key() == config.admin
         @ ErrorCode::InvalidPoolAuthority
     )]
     pub pool_authority: Signer<'info>,
     pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_metadata_program: Program<'info, Metaplex>,
 }
 
 #[derive(Accounts)]
-pub struct MintTokens<'info> {
+pub struct MintNodeNft<'info> {
     #[account(
-        seeds = [b"pool_state"],
+        seeds = [b"config"],
         bump,
     )]
-    pub pool_state: Account<'info, PoolState>,
+    pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [b"mint"],
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
-        mint::authority = mint
     )]
-    pub mint: Account<'info, Mint>,
+    pub pool_state: Box<Account<'info, PoolState>>,
     #[account(
-        mut,
-        associated_token::mint = mint,
-        associated_token::authority = pool_authority
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
     )]
-    pub treasury_vault: Account<'info, TokenAccount>,
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
     #[account(
         mut,
-        associated_token::mint = mint,
-        associated_token::authority = pool_state
+        seeds = [b"node_collection_state", pool_state.key().as_ref()],
+        bump,
     )]
-    pub selling_vault: Account<'info, TokenAccount>,
+    pub node_collection: Box<Account<'info, NodeCollection>>,
     #[account(
         mut,
-        constraint = pool_authority.key() == POOL_AUTHORITY
-        @ ErrorCode::InvalidPoolAuthority
+        address = pool_state.node_collection
+        @ ErrorCode::NodeNftDisabled
     )]
-    pub pool_authority: Signer<'info>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct MintNodes<'info> {
+    pub collection_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub collection_metadata: UncheckedAccount<'info>,
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub collection_master_edition: UncheckedAccount<'info>,
     #[account(
-        mut,
-        seeds = [b"pool_state"],
+        init,
+        seeds = [b"node_nft_mint", pool_state.key().as_ref(), node_collection.minted_nodes.to_le_bytes().as_ref()],
         bump,
+        payer = user,
+        mint::decimals = 0,
+        mint::authority = pool_state,
+        mint::freeze_authority = pool_state,
     )]
-    pub pool_state: Account<'info, PoolState>,
+    pub node_mint: Box<Account<'info, Mint>>,
     #[account(
-        constraint = pool_authority.key() == POOL_AUTHORITY
-        @ ErrorCode::InvalidPoolAuthority
+        init,
+        payer = user,
+        space = 8 + NodeNft::SPACE,
+        seeds = [b"node_nft", pool_state.key().as_ref(), node_mint.key().as_ref()],
+        bump,
     )]
-    pub pool_authority: Signer<'info>,
-}
-
-#[derive(Accounts)]
-pub struct InitializeUserStake<'info> {
+    pub node_nft: Box<Account<'info, NodeNft>>,
     #[account(
         init,
         payer = user,
-        space = 8 + UserStakeEntry::SPACE,
-        seeds = [user.key().as_ref()],
-        bump
+        associated_token::mint = node_mint,
+        associated_token::authority = user
     )]
-    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    pub user_nft_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub node_metadata: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub node_master_edition: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_stake_entry: Box<Account<'info, UserStakeEntry>>,
     #[account(mut)]
     pub user: Signer<'info>,
-    pub system_program: Program<'info, System>
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_metadata_program: Program<'info, Metaplex>,
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct PresaleNodes<'info> {
+pub struct ClaimNodeNft<'info> {
     #[account(
-        mut,
-        seeds = [user.key().as_ref()],
+        seeds = [b"config"],
         bump,
     )]
-    pub user_stake_entry: Account<'info, UserStakeEntry>,
+    pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [b"presale_vault"],
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
     )]
-    pub presale_vault: Account<'info, PresaleVault>,
+    pub pool_state: Account<'info, PoolState>,
     #[account(
-        mut,
-        seeds = [b"pool_state"],
+        seeds = [b"presale_state", pool_state.key().as_ref(), [FIRST_PRESALE_ROUND].as_ref()],
         bump,
     )]
-    pub pool_state: Account<'info, PoolState>,
     pub presale_state: Account<'info, PresaleState>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    pub clock: Sysvar<'info, Clock>,
-}
-
-#[derive(Accounts)]
-pub struct WithdrawCap<'info> {
+    #[account(
+        address = pool_state.selling_mint,
+    )]
+    pub selling_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
     #[account(
         mut,
-        seeds = [b"presale_vault"],
-        bump
+        seeds = [b"reward_vault", pool_state.key().as_ref()],
+        bump,
     )]
-    pub presale_vault: Account<'info, PresaleVault>,
+    pub reward_vault: Account<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = withdrawer.key() == VAULT_AUTHORITY
-        @ ErrorCode::InvalidVaultAuthority
+        seeds = [b"node_nft", pool_state.key().as_ref(), node_nft.mint.as_ref()],
+        bump,
     )]
-    pub withdrawer: Signer<'info>,
+    pub node_nft: Account<'info, NodeNft>,
+    #[account(
+        constraint = holder_nft_account.owner == holder.key()
+        @ ErrorCode::InvalidNodeNftHolder,
+        constraint = holder_nft_account.mint == node_nft.mint
+        @ ErrorCode::InvalidNodeNftHolder,
+        constraint = holder_nft_account.amount == 1
+        @ ErrorCode::InvalidNodeNftHolder,
+    )]
+    pub holder_nft_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = holder_token_account.owner == holder.key(),
+        constraint = holder_token_account.mint == selling_mint.key(),
+    )]
+    pub holder_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        space = 8 + RewardVesting::SPACE,
+        seeds = [b"vesting", pool_state.key().as_ref(), holder.key().as_ref()],
+        bump,
+    )]
+    pub reward_vesting: Account<'info, RewardVesting>,
+    #[account(mut)]
+    pub holder: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
-
-impl<'info> WithdrawCap<'info> {
-    pub fn send_lamports_from_vault_to_owner(&mut self) -> Result<()> {
-        let presale_vault = self.presale_vault.to_account_info();
-        let presale_vault_data_len = presale_vault.try_data_len()?;
-        let presale_vault_minimum_rent_exempt_balance = self.rent.minimum_balance(presale_vault_data_len);
-        let all_presale_vault_lamports = **presale_vault.try_borrow_lamports()?;
-        let available_lamports = all_presale_vault_lamports - presale_vault_minimum_rent_exempt_balance;
-
-        **presale_vault.try_borrow_mut_lamports()? -= available_lamports;
-        **self.withdrawer.try_borrow_mut_lamports()? += available_lamports;
-
-        Ok(())
-    }
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
 }
 
 #[derive(Accounts)]
-pub struct CreateNodes<'info> {
+pub struct BurnNodeNft<'info> {
     #[account(
-        seeds = [b"presale_state"],
+        seeds = [b"config"],
         bump,
     )]
-    pub presale_state: Account<'info, PresaleState>,
+    pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [b"pool_state"],
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
         bump,
-        // has_one = selling_mint,
-        // has_one = selling_vault,
     )]
-    pub pool_state: Account<'info, PoolState>,
+    pub pool_state: Box<Account<'info, PoolState>>,
     #[account(
-        seeds = [b"mint"],
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
         bump,
-        mint::authority = selling_mint,
     )]
-    pub selling_mint: Account<'info, Mint>,
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
     #[account(
         mut,
-        associated_token::mint = selling_mint,
-        associated_token::authority = POOL_AUTHORITY,
+        close = holder,
+        seeds = [b"node_nft", pool_state.key().as_ref(), node_nft.mint.as_ref()],
+        bump,
     )]
-    pub treasury_vault: Account<'info, TokenAccount>,
+    pub node_nft: Box<Account<'info, NodeNft>>,
     #[account(
         mut,
-        associated_token::mint = selling_mint,
-        associated_token::authority = pool_state,
+        address = node_nft.mint,
     )]
-    pub selling_vault: Account<'info, TokenAccount>,
+    pub node_mint: Box<Account<'info, Mint>>,
     #[account(
         mut,
-        constraint = user_token_account.owner == user.key(),
-        constraint = user_token_account.mint == selling_mint.key(),
+        constraint = holder_nft_account.owner == holder.key()
+        @ ErrorCode::InvalidNodeNftHolder,
+        constraint = holder_nft_account.mint == node_nft.mint
+        @ ErrorCode::InvalidNodeNftHolder,
+        constraint = holder_nft_account.amount == 1
+        @ ErrorCode::InvalidNodeNftHolder,
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub holder_nft_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub node_metadata: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the master edition account managed by the Metadata program
+    pub node_master_edition: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we are interacting with the metadata account managed by the Metadata program
+    pub collection_metadata: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        space = 8 + UserStakeEntry::SPACE,
+        seeds = [pool_state.key().as_ref(), holder.key().as_ref()],
+        bump,
+    )]
+    pub holder_stake_entry: Box<Account<'info, UserStakeEntry>>,
+    // only needed when the holder is not the wallet that wrapped the node
     #[account(
         mut,
-        seeds = [user.key().as_ref()],
+        seeds = [pool_state.key().as_ref(), node_nft.wrapped_by.as_ref()],
         bump,
+        constraint = node_nft.wrapped_by != holder.key()
+        @ ErrorCode::MissingWrapperStakeEntry,
     )]
-    pub user_stake_entry: Account<'info, UserStakeEntry>,
-    pub user: Signer<'info>,
+    pub wrapper_stake_entry: Option<Box<Account<'info, UserStakeEntry>>>,
+    #[account(mut)]
+    pub holder: Signer<'info>,
+    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    pub token_metadata_program: Program<'info, Metaplex>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct TransferNodes<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"pool_state", pool_state.pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub pool_state: Account<'info, PoolState>,
+    #[account(
+        seeds = [b"emission_schedule", pool_state.key().as_ref()],
+        bump,
+    )]
+    pub emission_schedule: Option<Account<'info, EmissionSchedule>>,
+    #[account(
+        mut,
+        seeds = [pool_state.key().as_ref(), sender.key().as_ref()],
+        bump,
+    )]
+    pub sender_stake_entry: Account<'info, UserStakeEntry>,
+    #[account(
+        init_if_needed,
+        payer = sender,
+        space = 8 + UserStakeEntry::SPACE,
+        seeds = [pool_state.key().as_ref(), recipient.key().as_ref()],
+        bump,
+    )]
+    pub recipient_stake_entry: Account<'info, UserStakeEntry>,
+    pub recipient: SystemAccount<'info>,
+    #[account(mut)]
+    pub sender: Signer<'info>,
     pub system_program: Program<'info, System>,
     pub clock: Sysvar<'info, Clock>,
-}
\ No newline at end of file
+}
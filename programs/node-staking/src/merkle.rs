@@ -0,0 +1,97 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::keccak,
+};
+
+/// Leaf committing a wallet to its presale allocation:
+/// `keccak256(wallet || allocation as little endian u16)`.
+pub fn allocation_leaf(wallet: &Pubkey, allocation: u16) -> [u8; 32] {
+    keccak::hashv(&[wallet.as_ref(), &allocation.to_le_bytes()]).to_bytes()
+}
+
+/// Verifies `leaf` against `root`. Pairs are hashed in sorted order, so
+/// proofs don't need to carry left/right positions.
+pub fn verify_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).to_bytes()
+        } else {
+            keccak::hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[a, b]).to_bytes()
+        } else {
+            keccak::hashv(&[b, a]).to_bytes()
+        }
+    }
+
+    fn wallets() -> Vec<(Pubkey, u16)> {
+        (1..=4u8).map(|seed| (Pubkey::new_from_array([seed; 32]), u16::from(seed) * 10)).collect()
+    }
+
+    // root and proofs of a four leaf tree
+    fn tree(leaves: &[[u8; 32]; 4]) -> ([u8; 32], Vec<Vec<[u8; 32]>>) {
+        let left = hash_pair(&leaves[0], &leaves[1]);
+        let right = hash_pair(&leaves[2], &leaves[3]);
+        let root = hash_pair(&left, &right);
+        let proofs = vec![
+            vec![leaves[1], right],
+            vec![leaves[0], right],
+            vec![leaves[3], left],
+            vec![leaves[2], left],
+        ];
+
+        (root, proofs)
+    }
+
+    #[test]
+    fn verifies_every_whitelisted_allocation() {
+        let wallets = wallets();
+        let leaves: [[u8; 32]; 4] = std::array::from_fn(|index| allocation_leaf(&wallets[index].0, wallets[index].1));
+        let (root, proofs) = tree(&leaves);
+
+        for (index, (wallet, allocation)) in wallets.iter().enumerate() {
+            assert!(verify_proof(&proofs[index], &root, allocation_leaf(wallet, *allocation)));
+        }
+    }
+
+    #[test]
+    fn rejects_other_allocation_wallet_or_proof() {
+        let wallets = wallets();
+        let leaves: [[u8; 32]; 4] = std::array::from_fn(|index| allocation_leaf(&wallets[index].0, wallets[index].1));
+        let (root, proofs) = tree(&leaves);
+        let (wallet, allocation) = wallets[0];
+
+        assert!(!verify_proof(&proofs[0], &root, allocation_leaf(&wallet, allocation + 1)));
+        assert!(!verify_proof(&proofs[0], &root, allocation_leaf(&Pubkey::new_unique(), allocation)));
+        assert!(!verify_proof(&proofs[1], &root, allocation_leaf(&wallet, allocation)));
+        assert!(!verify_proof(&[], &root, allocation_leaf(&wallet, allocation)));
+    }
+
+    #[test]
+    fn single_wallet_root_is_its_leaf() {
+        let wallet = Pubkey::new_unique();
+        let leaf = allocation_leaf(&wallet, 5);
+
+        assert!(verify_proof(&[], &leaf, leaf));
+        assert!(!verify_proof(&[], &leaf, allocation_leaf(&wallet, 6)));
+    }
+
+    #[test]
+    fn leaf_commits_to_little_endian_allocation() {
+        let wallet = Pubkey::new_unique();
+        let expected = keccak::hashv(&[wallet.as_ref(), &[0x34, 0x12]]).to_bytes();
+
+        assert_eq!(allocation_leaf(&wallet, 0x1234), expected);
+    }
+}
@@ -0,0 +1,176 @@
+use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+
+pub fn signer_index(multisig: &Multisig, key: &Pubkey) -> Result<usize> {
+    multisig.signers
+        .iter()
+        .position(|signer| signer == key)
+        .ok_or(ErrorCode::InvalidMultisigSigner.into())
+}
+
+pub fn validate_multisig(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(!signers.is_empty() && signers.len() <= Multisig::MAX_SIGNERS, ErrorCode::InvalidMultisigSettings);
+    require!(threshold > 0 && usize::from(threshold) <= signers.len(), ErrorCode::InvalidMultisigSettings);
+
+    for (index, signer) in signers.iter().enumerate() {
+        require!(!signers[..index].contains(signer), ErrorCode::InvalidMultisigSettings);
+    }
+
+    Ok(())
+}
+
+pub fn approve(proposal: &mut Proposal, multisig: &Multisig, signer: &Pubkey) -> Result<()> {
+    // approvals are bits per signer index, which only hold for the signers they were given for
+    require!(proposal.signers_version == multisig.signers_version, ErrorCode::StaleProposal);
+
+    let index = signer_index(multisig, signer)?;
+    require!(proposal.approvals & (1 << index) == 0, ErrorCode::AlreadyApproved);
+    proposal.approvals |= 1 << index;
+
+    Ok(())
+}
+
+/// Marks `proposal` as executed once it is approved by enough signers, is
+/// still live, and encodes exactly `action` on `pool_state`.
+pub fn consume_proposal(proposal: &mut Proposal, multisig: &Multisig, pool_state: &Pubkey, action: &ProposalAction, now: i64) -> Result<()> {
+    require!(!proposal.is_executed, ErrorCode::ProposalAlreadyExecuted);
+    require!(now <= proposal.expires_at, ErrorCode::ProposalExpired);
+    require!(proposal.signers_version == multisig.signers_version, ErrorCode::StaleProposal);
+    require!(proposal.approvals.count_ones() >= u32::from(multisig.threshold), ErrorCode::ProposalNotApproved);
+    require!(proposal.action == *action && proposal.pool_state == *pool_state, ErrorCode::InvalidProposalAction);
+
+    proposal.is_executed = true;
+
+    Ok(())
+}
+
+/// Applies a multisig only `action`, changing the signers invalidates the
+/// approvals of every other live proposal.
+pub fn apply_multisig_action(multisig: &mut Multisig, action: &ProposalAction) -> Result<()> {
+    let mut signers = multisig.signers.clone();
+    let mut threshold = multisig.threshold;
+
+    match *action {
+        ProposalAction::ChangeThreshold { threshold: new_threshold } => threshold = new_threshold,
+        ProposalAction::AddSigner { signer } => signers.push(signer),
+        ProposalAction::RemoveSigner { signer } => {
+            signers.remove(signer_index(multisig, &signer)?);
+        }
+        _ => return err!(ErrorCode::InvalidProposalAction),
+    }
+
+    validate_multisig(&signers, threshold)?;
+
+    if signers != multisig.signers {
+        multisig.signers_version = multisig.signers_version.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+        multisig.signers = signers;
+    }
+    multisig.threshold = threshold;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multisig(signers: &[Pubkey], threshold: u8) -> Multisig {
+        Multisig { signers: signers.to_vec(), threshold, proposal_count: 0, signers_version: 0 }
+    }
+
+    fn proposal(action: ProposalAction, expires_at: i64) -> Proposal {
+        Proposal {
+            id: 0,
+            proposer: Pubkey::default(),
+            pool_state: Pubkey::default(),
+            action,
+            approvals: 0,
+            expires_at,
+            is_executed: false,
+            signers_version: 0,
+        }
+    }
+
+    #[test]
+    fn two_of_three_executes_once_approved_by_two_signers() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let multisig = multisig(&signers, 2);
+        let action = ProposalAction::MintNodes { amount: 10 };
+        let mut proposal = proposal(action.clone(), 100);
+
+        approve(&mut proposal, &multisig, &signers[0]).unwrap();
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 0).is_err());
+
+        approve(&mut proposal, &multisig, &signers[2]).unwrap();
+        consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 0).unwrap();
+        assert!(proposal.is_executed);
+
+        // a proposal only authorizes one execution
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 0).is_err());
+    }
+
+    #[test]
+    fn approvals_count_each_signer_once() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let multisig = multisig(&signers, 2);
+        let mut proposal = proposal(ProposalAction::MintTokens { amount: 1 }, 100);
+
+        approve(&mut proposal, &multisig, &signers[1]).unwrap();
+        assert!(approve(&mut proposal, &multisig, &signers[1]).is_err());
+        assert!(approve(&mut proposal, &multisig, &Pubkey::new_unique()).is_err());
+        assert_eq!(proposal.approvals.count_ones(), 1);
+    }
+
+    #[test]
+    fn consume_proposal_rejects_expired_and_other_actions() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let multisig = multisig(&signers, 2);
+        let action = ProposalAction::WithdrawCap { round_id: 0 };
+        let mut proposal = proposal(action.clone(), 100);
+
+        approve(&mut proposal, &multisig, &signers[0]).unwrap();
+        approve(&mut proposal, &multisig, &signers[1]).unwrap();
+
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 101).is_err());
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &ProposalAction::WithdrawCap { round_id: 1 }, 100).is_err());
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::new_unique(), &action, 100).is_err());
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 100).is_ok());
+    }
+
+    #[test]
+    fn signer_changes_keep_the_threshold_reachable() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut multisig = multisig(&signers, 2);
+        let new_signer = Pubkey::new_unique();
+
+        apply_multisig_action(&mut multisig, &ProposalAction::AddSigner { signer: new_signer }).unwrap();
+        assert_eq!(multisig.signers.len(), 4);
+        assert!(apply_multisig_action(&mut multisig, &ProposalAction::AddSigner { signer: new_signer }).is_err());
+
+        apply_multisig_action(&mut multisig, &ProposalAction::RemoveSigner { signer: signers[0] }).unwrap();
+        apply_multisig_action(&mut multisig, &ProposalAction::RemoveSigner { signer: signers[1] }).unwrap();
+        assert_eq!(multisig.signers, vec![signers[2], new_signer]);
+
+        // two signers can't keep a threshold of two with one of them removed
+        assert!(apply_multisig_action(&mut multisig, &ProposalAction::RemoveSigner { signer: new_signer }).is_err());
+        assert!(apply_multisig_action(&mut multisig, &ProposalAction::ChangeThreshold { threshold: 3 }).is_err());
+        assert!(apply_multisig_action(&mut multisig, &ProposalAction::MintNodes { amount: 1 }).is_err());
+    }
+
+    #[test]
+    fn signer_changes_void_pending_approvals() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut multisig = multisig(&signers, 2);
+        let action = ProposalAction::MintNodes { amount: 10 };
+        let mut proposal = proposal(action.clone(), 100);
+
+        approve(&mut proposal, &multisig, &signers[0]).unwrap();
+        approve(&mut proposal, &multisig, &signers[1]).unwrap();
+
+        apply_multisig_action(&mut multisig, &ProposalAction::RemoveSigner { signer: signers[0] }).unwrap();
+
+        assert!(approve(&mut proposal, &multisig, &signers[2]).is_err());
+        assert!(consume_proposal(&mut proposal, &multisig, &Pubkey::default(), &action, 0).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::system_program::{create_account, CreateAccount};
+use anchor_spl::{
+    associated_token::{create, get_associated_token_address, Create},
+    metadata::{
+        create_master_edition_v3,
+        create_metadata_accounts_v3,
+        verify_sized_collection_item,
+        mpl_token_metadata::types::{Collection, DataV2},
+        CreateMasterEditionV3,
+        CreateMetadataAccountsV3,
+        VerifySizedCollectionItem,
+    },
+    token::{initialize_mint2, mint_to, spl_token, InitializeMint2, MintTo},
+};
+use crate::account::*;
+use crate::error::ErrorCode;
+
+// accounts of each node NFT minted with a purchase, passed in `remaining_accounts` in this order:
+// node mint, node NFT state, token account of the buyer, metadata and master edition
+pub const NODE_NFT_ACCOUNTS: usize = 5;
+
+/// Accounts shared by every node NFT minted into a pool's collection.
+pub struct NodeNftMinter<'info> {
+    pub pool_state: AccountInfo<'info>,
+    pub collection_mint: AccountInfo<'info>,
+    pub collection_metadata: AccountInfo<'info>,
+    pub collection_master_edition: AccountInfo<'info>,
+    pub owner: AccountInfo<'info>,      // receives the NFTs and pays for their accounts
+    pub rent: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    pub token_metadata_program: AccountInfo<'info>,
+}
+
+impl<'info> NodeNftMinter<'info> {
+    /// Mints a node NFT to `nft_account`, creates its metadata and master
+    /// edition and verifies it as an item of the pool's collection.
+    pub fn mint_nft(
+        &self,
+        node_collection: &NodeCollection,
+        node_mint: &AccountInfo<'info>,
+        nft_account: &AccountInfo<'info>,
+        node_metadata: &AccountInfo<'info>,
+        node_master_edition: &AccountInfo<'info>,
+        signer: &[&[&[u8]]],
+    ) -> Result<()> {
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.clone(),
+                MintTo {
+                    authority: self.pool_state.clone(),
+                    to: nft_account.clone(),
+                    mint: node_mint.clone()
+                },
+                signer
+            ),
+            1,
+        )?;
+
+        let node_data: DataV2 = DataV2 {
+            name: format!("{} #{}", node_collection.name, node_collection.minted_nodes),
+            symbol: node_collection.symbol.clone(),
+            uri: node_collection.uri.clone(),
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: Some(Collection {
+                verified: false,
+                key: node_collection.mint,
+            }),
+            uses: None,
+        };
+
+        create_metadata_accounts_v3(
+            CpiContext::new_with_signer(
+                self.token_metadata_program.clone(),
+                CreateMetadataAccountsV3 {
+                    payer: self.owner.clone(),
+                    update_authority: self.pool_state.clone(),
+                    mint: node_mint.clone(),
+                    metadata: node_metadata.clone(),
+                    mint_authority: self.pool_state.clone(),
+                    system_program: self.system_program.clone(),
+                    rent: self.rent.clone(),
+                },
+                signer
+            ),
+            node_data,
+            true,
+            true,
+            None,
+        )?;
+
+        create_master_edition_v3(
+            CpiContext::new_with_signer(
+                self.token_metadata_program.clone(),
+                CreateMasterEditionV3 {
+                    edition: node_master_edition.clone(),
+                    mint: node_mint.clone(),
+                    update_authority: self.pool_state.clone(),
+                    mint_authority: self.pool_state.clone(),
+                    payer: self.owner.clone(),
+                    metadata: node_metadata.clone(),
+                    token_program: self.token_program.clone(),
+                    system_program: self.system_program.clone(),
+                    rent: self.rent.clone(),
+                },
+                signer
+            ),
+            Some(0),
+        )?;
+
+        verify_sized_collection_item(
+            CpiContext::new_with_signer(
+                self.token_metadata_program.clone(),
+                VerifySizedCollectionItem {
+                    payer: self.owner.clone(),
+                    metadata: node_metadata.clone(),
+                    collection_authority: self.pool_state.clone(),
+                    collection_mint: self.collection_mint.clone(),
+                    collection_metadata: self.collection_metadata.clone(),
+                    collection_master_edition: self.collection_master_edition.clone(),
+                },
+                signer
+            ),
+            None,
+        )
+    }
+
+    /// Creates `amount` node NFTs from `remaining_accounts`, which holds
+    /// `NODE_NFT_ACCOUNTS` accounts per node.
+    pub fn create_nfts(
+        &self,
+        node_collection: &mut NodeCollection,
+        amount: u16,
+        remaining_accounts: &[AccountInfo<'info>],
+        reward_debt: u128,
+        signer: &[&[&[u8]]],
+    ) -> Result<()> {
+        require!(remaining_accounts.len() == usize::from(amount) * NODE_NFT_ACCOUNTS, ErrorCode::InvalidNodeNftAccounts);
+
+        for accounts in remaining_accounts.chunks(NODE_NFT_ACCOUNTS) {
+            self.create_nft(node_collection, accounts, reward_debt, signer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the next node NFT of the collection from `accounts` and mints
+    /// it to the owner. The node earns through its `NodeNft` state from
+    /// `reward_debt` on.
+    fn create_nft(
+        &self,
+        node_collection: &mut NodeCollection,
+        accounts: &[AccountInfo<'info>],
+        reward_debt: u128,
+        signer: &[&[&[u8]]],
+    ) -> Result<()> {
+        let [node_mint, node_nft, nft_account, node_metadata, node_master_edition] = accounts else {
+            return err!(ErrorCode::InvalidNodeNftAccounts);
+        };
+
+        let pool_key = self.pool_state.key();
+        let index = node_collection.minted_nodes.to_le_bytes();
+        let (mint_key, mint_bump) = Pubkey::find_program_address(&[b"node_nft_mint", pool_key.as_ref(), index.as_ref()], &crate::ID);
+        let (node_nft_key, node_nft_bump) = Pubkey::find_program_address(&[b"node_nft", pool_key.as_ref(), mint_key.as_ref()], &crate::ID);
+
+        require_keys_eq!(node_mint.key(), mint_key, ErrorCode::InvalidNodeNftAccounts);
+        require_keys_eq!(node_nft.key(), node_nft_key, ErrorCode::InvalidNodeNftAccounts);
+        require_keys_eq!(nft_account.key(), get_associated_token_address(&self.owner.key(), &mint_key), ErrorCode::InvalidNodeNftAccounts);
+
+        let rent = Rent::get()?;
+
+        create_account(
+            CpiContext::new_with_signer(
+                self.system_program.clone(),
+                CreateAccount {
+                    from: self.owner.clone(),
+                    to: node_mint.clone(),
+                },
+                &[&[b"node_nft_mint", pool_key.as_ref(), index.as_ref(), &[mint_bump]]]
+            ),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &self.token_program.key(),
+        )?;
+
+        initialize_mint2(
+            CpiContext::new(
+                self.token_program.clone(),
+                InitializeMint2 {
+                    mint: node_mint.clone(),
+                }
+            ),
+            0,
+            &pool_key,
+            Some(&pool_key),
+        )?;
+
+        create(
+            CpiContext::new(
+                self.associated_token_program.clone(),
+                Create {
+                    payer: self.owner.clone(),
+                    associated_token: nft_account.clone(),
+                    authority: self.owner.clone(),
+                    mint: node_mint.clone(),
+                    system_program: self.system_program.clone(),
+                    token_program: self.token_program.clone(),
+                }
+            )
+        )?;
+
+        let space = 8 + NodeNft::SPACE;
+        create_account(
+            CpiContext::new_with_signer(
+                self.system_program.clone(),
+                CreateAccount {
+                    from: self.owner.clone(),
+                    to: node_nft.clone(),
+                },
+                &[&[b"node_nft", pool_key.as_ref(), mint_key.as_ref(), &[node_nft_bump]]]
+            ),
+            rent.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+
+        let node_nft_state = NodeNft {
+            mint: mint_key,
+            reward_debt,
+            claimable_amount: 0,
+            wrapped_by: self.owner.key(),
+        };
+        node_nft_state.try_serialize(&mut &mut node_nft.try_borrow_mut_data()?[..])?;
+
+        self.mint_nft(node_collection, node_mint, nft_account, node_metadata, node_master_edition, signer)?;
+
+        node_collection.minted_nodes = node_collection.minted_nodes.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+use crate::helper::*;
+
+// owner of the Pyth v2 price accounts on mainnet and devnet
+pub const PYTH_PROGRAM_ID: Pubkey = pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+
+// offsets in a Pyth v2 price account
+const MAGIC: u32 = 0xa1b2_c3d4;
+const PRICE_ACCOUNT_TYPE: u32 = 3;
+const TRADING_STATUS: u32 = 1;
+const EXPO_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+// `usd_price_per_node` is in micro dollars
+const USD_DECIMALS: u32 = 6;
+const LAMPORTS_DECIMALS: u32 = 9;
+
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    data[offset..offset + N].try_into().unwrap()
+}
+
+/// Reads the aggregate price of a Pyth-format price account.
+pub fn load_price(price_feed: &AccountInfo) -> Result<OraclePrice> {
+    require_keys_eq!(*price_feed.owner, PYTH_PROGRAM_ID, ErrorCode::InvalidPriceFeed);
+
+    let data = price_feed.try_borrow_data()?;
+
+    require!(data.len() >= PRICE_ACCOUNT_MIN_LEN, ErrorCode::InvalidPriceFeed);
+    require!(u32::from_le_bytes(read_bytes(&data, 0)) == MAGIC, ErrorCode::InvalidPriceFeed);
+    require!(u32::from_le_bytes(read_bytes(&data, 8)) == PRICE_ACCOUNT_TYPE, ErrorCode::InvalidPriceFeed);
+    require!(u32::from_le_bytes(read_bytes(&data, AGG_STATUS_OFFSET)) == TRADING_STATUS, ErrorCode::InvalidPriceFeed);
+
+    Ok(OraclePrice {
+        price: i64::from_le_bytes(read_bytes(&data, AGG_PRICE_OFFSET)),
+        conf: u64::from_le_bytes(read_bytes(&data, AGG_CONF_OFFSET)),
+        expo: i32::from_le_bytes(read_bytes(&data, EXPO_OFFSET)),
+        publish_time: i64::from_le_bytes(read_bytes(&data, TIMESTAMP_OFFSET)),
+    })
+}
+
+/// Rejects prices older than `max_price_age` or with a confidence interval
+/// wider than `max_confidence_bps` of the price.
+pub fn validate_price(oracle_price: &OraclePrice, presale_state: &PresaleState, now: i64) -> Result<()> {
+    require!(oracle_price.price > 0, ErrorCode::InvalidPriceFeed);
+    require!(now.saturating_sub(oracle_price.publish_time) <= presale_state.max_price_age, ErrorCode::StalePrice);
+
+    let max_conf = apply_basis_points(oracle_price.price as u64, presale_state.max_confidence_bps)?;
+    require!(oracle_price.conf <= max_conf, ErrorCode::PriceConfidenceTooWide);
+
+    Ok(())
+}
+
+/// Lamports worth `usd_amount` micro dollars at a SOL/USD `oracle_price`.
+pub fn usd_to_lamports(usd_amount: u64, oracle_price: &OraclePrice) -> Result<u64> {
+    let price = oracle_price.price as u128;
+    let scale = 10u128.pow(LAMPORTS_DECIMALS - USD_DECIMALS);
+    let numerator = u128::from(usd_amount).checked_mul(scale).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+
+    let lamports = if oracle_price.expo < 0 {
+        let expo_scale = 10u128.checked_pow(oracle_price.expo.unsigned_abs()).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        numerator.checked_mul(expo_scale).ok_or(ErrorCode::UnableCalculatingNodesPrice)? / price
+    } else {
+        let expo_scale = 10u128.checked_pow(oracle_price.expo as u32).ok_or(ErrorCode::UnableCalculatingNodesPrice)?;
+        numerator / price.checked_mul(expo_scale).ok_or(ErrorCode::UnableCalculatingNodesPrice)?
+    };
+
+    u64::try_from(lamports).map_err(|_| ErrorCode::UnableCalculatingNodesPrice.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oracle_price(price: i64, expo: i32) -> OraclePrice {
+        OraclePrice { price, conf: 0, expo, publish_time: 0 }
+    }
+
+    fn presale(max_price_age: i64, max_confidence_bps: u16) -> PresaleState {
+        let mut presale_state = PresaleState::deserialize(&mut &[0u8; PresaleState::SPACE][..]).unwrap();
+        presale_state.max_price_age = max_price_age;
+        presale_state.max_confidence_bps = max_confidence_bps;
+        presale_state
+    }
+
+    fn price_account(price: i64, conf: u64, expo: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0u8; PRICE_ACCOUNT_MIN_LEN];
+        data[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        data[8..12].copy_from_slice(&PRICE_ACCOUNT_TYPE.to_le_bytes());
+        data[EXPO_OFFSET..EXPO_OFFSET + 4].copy_from_slice(&expo.to_le_bytes());
+        data[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + 8].copy_from_slice(&publish_time.to_le_bytes());
+        data[AGG_PRICE_OFFSET..AGG_PRICE_OFFSET + 8].copy_from_slice(&price.to_le_bytes());
+        data[AGG_CONF_OFFSET..AGG_CONF_OFFSET + 8].copy_from_slice(&conf.to_le_bytes());
+        data[AGG_STATUS_OFFSET..AGG_STATUS_OFFSET + 4].copy_from_slice(&TRADING_STATUS.to_le_bytes());
+        data
+    }
+
+    fn load_owned_by(data: &mut [u8], owner: Pubkey) -> Result<OraclePrice> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let price_feed = AccountInfo::new(&key, false, false, &mut lamports, data, &owner, false, 0);
+
+        load_price(&price_feed)
+    }
+
+    fn load(data: &mut [u8]) -> Result<OraclePrice> {
+        load_owned_by(data, PYTH_PROGRAM_ID)
+    }
+
+    #[test]
+    fn converts_with_negative_expo() {
+        // $150.00000000 per SOL, $300 per node
+        let oracle_price = oracle_price(15_000_000_000, -8);
+
+        assert_eq!(usd_to_lamports(300_000_000, &oracle_price).unwrap(), 2_000_000_000);
+        assert_eq!(usd_to_lamports(1, &oracle_price).unwrap(), 6);
+    }
+
+    #[test]
+    fn converts_with_positive_and_zero_expo() {
+        assert_eq!(usd_to_lamports(300_000_000, &oracle_price(15, 1)).unwrap(), 2_000_000_000);
+        assert_eq!(usd_to_lamports(300_000_000, &oracle_price(150, 0)).unwrap(), 2_000_000_000);
+    }
+
+    #[test]
+    fn conversion_rejects_unrepresentable_amounts() {
+        assert!(usd_to_lamports(u64::MAX, &oracle_price(1, -8)).is_err());
+        assert!(usd_to_lamports(1, &oracle_price(1, 40)).is_err());
+    }
+
+    #[test]
+    fn validate_price_checks_age_and_confidence() {
+        let presale_state = presale(60, 100);
+        let mut oracle_price = oracle_price(10_000, -2);
+        oracle_price.publish_time = 1_000;
+        oracle_price.conf = 100;
+
+        assert!(validate_price(&oracle_price, &presale_state, 1_060).is_ok());
+        assert!(validate_price(&oracle_price, &presale_state, 1_061).is_err());
+
+        oracle_price.conf = 101;
+        assert!(validate_price(&oracle_price, &presale_state, 1_000).is_err());
+
+        oracle_price.conf = 0;
+        oracle_price.price = 0;
+        assert!(validate_price(&oracle_price, &presale_state, 1_000).is_err());
+    }
+
+    #[test]
+    fn loads_aggregate_price() {
+        let mut data = price_account(15_000_000_000, 1_000_000, -8, 1_700_000_000);
+        let oracle_price = load(&mut data).unwrap();
+
+        assert_eq!(oracle_price.price, 15_000_000_000);
+        assert_eq!(oracle_price.conf, 1_000_000);
+        assert_eq!(oracle_price.expo, -8);
+        assert_eq!(oracle_price.publish_time, 1_700_000_000);
+    }
+
+    #[test]
+    fn rejects_other_accounts_and_halted_prices() {
+        let mut short = vec![0u8; PRICE_ACCOUNT_MIN_LEN - 1];
+        assert!(load(&mut short).is_err());
+
+        let mut wrong_magic = price_account(1, 0, 0, 0);
+        wrong_magic[0] = 0;
+        assert!(load(&mut wrong_magic).is_err());
+
+        let mut halted = price_account(1, 0, 0, 0);
+        halted[AGG_STATUS_OFFSET..AGG_STATUS_OFFSET + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(load(&mut halted).is_err());
+
+        let mut not_pyth = price_account(1, 0, 0, 0);
+        assert!(load_owned_by(&mut not_pyth, Pubkey::new_unique()).is_err());
+    }
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+
+/// Moves commission earned in an ended presale round to `pending_lamports`
+/// if the round reached its soft cap, or drops it if the round is refunded.
+pub fn settle_presale_commission(referral_account: &mut ReferralAccount, presale_state: &PresaleState, now: i64) -> Result<()> {
+    if referral_account.locked_lamports == 0 {
+        return Ok(());
+    }
+
+    require!(presale_state.round_id == referral_account.locked_round, ErrorCode::InvalidReferralRound);
+    require!(presale_state.presale_end_at < now, ErrorCode::NotEndedPresale);
+
+    if presale_state.is_soft_cap_reached() {
+        referral_account.pending_lamports = referral_account.pending_lamports
+            .checked_add(referral_account.locked_lamports)
+            .ok_or(ErrorCode::UnavailableCaculateSum)?;
+    }
+    referral_account.locked_lamports = 0;
+
+    Ok(())
+}
+
+/// Books presale commission, which stays locked until the round is known to
+/// have reached its soft cap.
+pub fn lock_presale_commission(referral_account: &mut ReferralAccount, round_id: u8, amount: u64) -> Result<()> {
+    require!(
+        referral_account.locked_lamports == 0 || referral_account.locked_round == round_id,
+        ErrorCode::InvalidReferralRound
+    );
+
+    referral_account.locked_lamports = referral_account.locked_lamports.checked_add(amount).ok_or(ErrorCode::UnavailableCaculateSum)?;
+    referral_account.locked_round = round_id;
+
+    Ok(())
+}
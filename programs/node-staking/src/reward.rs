@@ -0,0 +1,384 @@
+use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+
+pub const SECONDS_PER_DAY: u128 = 86_400;
+
+/// `PoolState::acc_reward_per_node` and `UserStakeEntry::reward_debt` are kept
+/// in base units multiplied by this factor. Since `reward_per_node` is a daily
+/// rate, scaling by the seconds in a day keeps per-second accrual exact and
+/// the only truncation happens once, when rewards are moved to
+/// `claimable_amount`.
+pub const ACC_REWARD_PRECISION: u128 = SECONDS_PER_DAY;
+
+/// Advances the global accumulator to `now`. Pools with an emission schedule
+/// are paid by integrating its epochs; otherwise the flat `reward_per_node`
+/// applies. Must be called before anything that changes a stake or the reward
+/// rate, so a new rate only applies from the moment it is set.
+pub fn update_pool(
+    pool_state: &mut PoolState,
+    emission_schedule: Option<&EmissionSchedule>,
+    now: i64,
+) -> Result<()> {
+    if now <= pool_state.last_update_ts {
+        return Ok(());
+    }
+
+    // nothing is owed while no node is staked, the emission of that time is skipped
+    if pool_state.total_staked_nodes == 0 {
+        pool_state.last_update_ts = now;
+        return Ok(());
+    }
+
+    let increment = if pool_state.emission_schedule == Pubkey::default() {
+        emitted(pool_state.reward_per_node, now - pool_state.last_update_ts)?
+    } else {
+        let emission_schedule = emission_schedule.ok_or(ErrorCode::MissingEmissionSchedule)?;
+        scheduled_emission(emission_schedule, pool_state.last_update_ts, now)?
+    };
+
+    let liabilities = increment.checked_mul(pool_state.total_staked_nodes.into()).ok_or(ErrorCode::UnableCalculatingRewards)?;
+
+    pool_state.acc_reward_per_node = pool_state.acc_reward_per_node.checked_add(increment).ok_or(ErrorCode::UnableCalculatingRewards)?;
+    pool_state.reward_liabilities = pool_state.reward_liabilities.checked_add(liabilities).ok_or(ErrorCode::UnableCalculatingRewards)?;
+    pool_state.last_update_ts = now;
+
+    Ok(())
+}
+
+/// Per-node emission between `from` and `to`, in `ACC_REWARD_PRECISION`
+/// units. Every epoch runs until the next one starts; the last one runs
+/// forever, halving every `halving_interval` if it is set.
+pub fn scheduled_emission(emission_schedule: &EmissionSchedule, from: i64, to: i64) -> Result<u128> {
+    let epochs = &emission_schedule.epochs;
+    let mut emission: u128 = 0;
+
+    for (index, epoch) in epochs.iter().enumerate() {
+        let epoch_end = epochs.get(index + 1).map_or(i64::MAX, |next| next.start_at);
+        let start = from.max(epoch.start_at);
+        let end = to.min(epoch_end);
+
+        if start >= end {
+            continue;
+        }
+
+        let is_last = index + 1 == epochs.len();
+        if !is_last || emission_schedule.halving_interval == 0 {
+            emission = emission
+                .checked_add(emitted(epoch.reward_per_node, end - start)?)
+                .ok_or(ErrorCode::UnableCalculatingRewards)?;
+            continue;
+        }
+
+        let mut cursor = start;
+        while cursor < end {
+            let halvings = (cursor - epoch.start_at) / emission_schedule.halving_interval;
+            if halvings >= i64::from(u64::BITS) {
+                break;
+            }
+
+            let reward_per_node = epoch.reward_per_node >> halvings;
+            if reward_per_node == 0 {
+                break;
+            }
+
+            let halving_end = epoch.start_at
+                .saturating_add((halvings + 1).saturating_mul(emission_schedule.halving_interval))
+                .min(end);
+            emission = emission
+                .checked_add(emitted(reward_per_node, halving_end - cursor)?)
+                .ok_or(ErrorCode::UnableCalculatingRewards)?;
+            cursor = halving_end;
+        }
+    }
+
+    Ok(emission)
+}
+
+fn emitted(reward_per_node: u64, period: i64) -> Result<u128> {
+    u128::from(reward_per_node).checked_mul(period as u128).ok_or(ErrorCode::UnableCalculatingRewards.into())
+}
+
+/// Checks that epochs are ordered, fit the account and the halving interval
+/// is not negative.
+pub fn validate_emission_schedule(epochs: &[EmissionEpoch], halving_interval: i64) -> Result<()> {
+    require!(!epochs.is_empty() && epochs.len() <= EmissionSchedule::MAX_EPOCHS, ErrorCode::InvalidEmissionSchedule);
+    require!(halving_interval >= 0, ErrorCode::InvalidEmissionSchedule);
+    require!(
+        epochs.windows(2).all(|pair| pair[0].start_at < pair[1].start_at),
+        ErrorCode::InvalidEmissionSchedule
+    );
+
+    Ok(())
+}
+
+/// Accumulated rewards of `nodes` at the pool's current accumulator, in
+/// `ACC_REWARD_PRECISION` units.
+pub fn accumulated_rewards(nodes: u16, pool_state: &PoolState) -> Result<u128> {
+    pool_state.acc_reward_per_node.checked_mul(nodes.into()).ok_or(ErrorCode::UnableCalculatingRewards.into())
+}
+
+/// Moves everything the entry has earned since its last settlement into
+/// `claimable_amount`. The pool must be updated first.
+pub fn settle_rewards(user_stake_entry: &mut UserStakeEntry, pool_state: &PoolState) -> Result<()> {
+    let accumulated = accumulated_rewards(user_stake_entry.staked_amount, pool_state)?;
+    let pending = accumulated.checked_sub(user_stake_entry.reward_debt).ok_or(ErrorCode::UnableCalculatingRewards)? / ACC_REWARD_PRECISION;
+    let pending = u64::try_from(pending).map_err(|_| ErrorCode::UnableCalculatingRewards)?;
+
+    user_stake_entry.claimable_amount = user_stake_entry.claimable_amount.checked_add(pending).ok_or(ErrorCode::UnavailableCaculateSum)?;
+    user_stake_entry.reward_debt = accumulated;
+
+    Ok(())
+}
+
+/// Same as `settle_rewards` for a single NFT-wrapped node.
+pub fn settle_node_nft_rewards(node_nft: &mut NodeNft, pool_state: &PoolState) -> Result<()> {
+    let accumulated = accumulated_rewards(1, pool_state)?;
+    let pending = accumulated.checked_sub(node_nft.reward_debt).ok_or(ErrorCode::UnableCalculatingRewards)? / ACC_REWARD_PRECISION;
+    let pending = u64::try_from(pending).map_err(|_| ErrorCode::UnableCalculatingRewards)?;
+
+    node_nft.claimable_amount = node_nft.claimable_amount.checked_add(pending).ok_or(ErrorCode::UnavailableCaculateSum)?;
+    node_nft.reward_debt = accumulated;
+
+    Ok(())
+}
+
+/// Outstanding rewards owed to stakers, in base units.
+pub fn reward_liabilities(pool_state: &PoolState) -> u64 {
+    u64::try_from(pool_state.reward_liabilities / ACC_REWARD_PRECISION).unwrap_or(u64::MAX)
+}
+
+/// Books `amount` as paid out of the reward vault.
+pub fn release_liabilities(pool_state: &mut PoolState, amount: u64) -> Result<()> {
+    let released = u128::from(amount).checked_mul(ACC_REWARD_PRECISION).ok_or(ErrorCode::UnableCalculatingRewards)?;
+    // settled amounts are truncated, so paying them can never exceed what was accrued
+    pool_state.reward_liabilities = pool_state.reward_liabilities.saturating_sub(released);
+
+    Ok(())
+}
+
+/// Re-bases the entry on the current accumulator after `staked_amount`
+/// changed, so the new nodes only earn from now on.
+pub fn sync_reward_debt(user_stake_entry: &mut UserStakeEntry, pool_state: &PoolState) -> Result<()> {
+    user_stake_entry.reward_debt = accumulated_rewards(user_stake_entry.staked_amount, pool_state)?;
+
+    Ok(())
+}
+
+/// Switches the pool to a queued update once its timelock is over. Rewards
+/// up to `now` are accrued at the old rate first.
+pub fn apply_pool_update(
+    pool_state: &mut PoolState,
+    pending_pool_update: &PendingPoolUpdate,
+    emission_schedule: Option<&EmissionSchedule>,
+    now: i64,
+) -> Result<()> {
+    require!(now >= pending_pool_update.eta, ErrorCode::PoolUpdateLocked);
+
+    update_pool(pool_state, emission_schedule, now)?;
+
+    pool_state.tokens_per_node = pending_pool_update.tokens_per_node;
+    pool_state.reward_per_node = pending_pool_update.reward_per_node;
+    pool_state.max_allocation = pending_pool_update.max_allocation;
+    pool_state.treasury_to_selling = pending_pool_update.treasury_to_selling;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(reward_per_node: u64, total_staked_nodes: u32) -> PoolState {
+        let mut pool_state = PoolState::deserialize(&mut &[0u8; PoolState::SPACE][..]).unwrap();
+        pool_state.reward_per_node = reward_per_node;
+        pool_state.total_staked_nodes = total_staked_nodes;
+        pool_state
+    }
+
+    fn entry(staked_amount: u16) -> UserStakeEntry {
+        let mut user_stake_entry = UserStakeEntry::deserialize(&mut &[0u8; UserStakeEntry::SPACE][..]).unwrap();
+        user_stake_entry.staked_amount = staked_amount;
+        user_stake_entry
+    }
+
+    fn schedule(epochs: &[(i64, u64)], halving_interval: i64) -> EmissionSchedule {
+        EmissionSchedule {
+            epochs: epochs.iter().map(|&(start_at, reward_per_node)| EmissionEpoch { start_at, reward_per_node }).collect(),
+            halving_interval,
+        }
+    }
+
+    #[test]
+    fn scheduled_emission_switches_rate_at_epoch_start() {
+        let emission_schedule = schedule(&[(0, 100), (10, 40)], 0);
+
+        assert_eq!(scheduled_emission(&emission_schedule, 0, 10).unwrap(), 1_000);
+        assert_eq!(scheduled_emission(&emission_schedule, 5, 15).unwrap(), 5 * 100 + 5 * 40);
+        assert_eq!(scheduled_emission(&emission_schedule, 10, 20).unwrap(), 400);
+    }
+
+    #[test]
+    fn scheduled_emission_pays_nothing_before_first_epoch() {
+        let emission_schedule = schedule(&[(100, 10)], 0);
+
+        assert_eq!(scheduled_emission(&emission_schedule, 0, 100).unwrap(), 0);
+        assert_eq!(scheduled_emission(&emission_schedule, 50, 110).unwrap(), 100);
+    }
+
+    #[test]
+    fn scheduled_emission_halves_last_epoch_across_boundaries() {
+        let emission_schedule = schedule(&[(0, 100), (10, 80)], 5);
+
+        // 10s at 100, then 80, 40 and 20 for the halvings of the last epoch
+        assert_eq!(scheduled_emission(&emission_schedule, 0, 22).unwrap(), 1_000 + 400 + 200 + 40);
+        // split intervals add up to the same total
+        let split = scheduled_emission(&emission_schedule, 0, 13).unwrap()
+            + scheduled_emission(&emission_schedule, 13, 17).unwrap()
+            + scheduled_emission(&emission_schedule, 17, 22).unwrap();
+        assert_eq!(split, 1_640);
+    }
+
+    #[test]
+    fn scheduled_emission_stops_once_rate_halves_to_zero() {
+        let emission_schedule = schedule(&[(0, 4)], 10);
+
+        // 4, 2, 1 and then nothing
+        assert_eq!(scheduled_emission(&emission_schedule, 0, 1_000).unwrap(), 40 + 20 + 10);
+    }
+
+    #[test]
+    fn validate_emission_schedule_rejects_bad_epochs() {
+        let epoch = |start_at| EmissionEpoch { start_at, reward_per_node: 1 };
+
+        assert!(validate_emission_schedule(&[epoch(0), epoch(10)], 0).is_ok());
+        assert!(validate_emission_schedule(&[], 0).is_err());
+        assert!(validate_emission_schedule(&[epoch(10), epoch(10)], 0).is_err());
+        assert!(validate_emission_schedule(&[epoch(0)], -1).is_err());
+    }
+
+    #[test]
+    fn update_pool_skips_time_without_staked_nodes() {
+        let mut pool_state = pool(10, 0);
+
+        update_pool(&mut pool_state, None, 1_000).unwrap();
+
+        assert_eq!(pool_state.acc_reward_per_node, 0);
+        assert_eq!(pool_state.reward_liabilities, 0);
+        assert_eq!(pool_state.last_update_ts, 1_000);
+    }
+
+    #[test]
+    fn settle_rewards_pays_nothing_while_pool_is_empty() {
+        let mut pool_state = pool(10, 0);
+        let mut user_stake_entry = entry(0);
+        let day = SECONDS_PER_DAY as i64;
+
+        update_pool(&mut pool_state, None, day).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        assert_eq!(user_stake_entry.claimable_amount, 0);
+        assert_eq!(reward_liabilities(&pool_state), 0);
+
+        // the first staker only earns from the moment it staked
+        user_stake_entry.staked_amount = 1;
+        pool_state.total_staked_nodes = 1;
+        sync_reward_debt(&mut user_stake_entry, &pool_state).unwrap();
+
+        update_pool(&mut pool_state, None, 2 * day).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        assert_eq!(user_stake_entry.claimable_amount, 10);
+    }
+
+    #[test]
+    fn settle_rewards_pays_flat_daily_rate() {
+        let mut pool_state = pool(2, 3);
+        let mut user_stake_entry = entry(3);
+
+        update_pool(&mut pool_state, None, SECONDS_PER_DAY as i64).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        assert_eq!(user_stake_entry.claimable_amount, 6);
+        assert_eq!(reward_liabilities(&pool_state), 6);
+
+        // settling again without time passing adds nothing
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+        assert_eq!(user_stake_entry.claimable_amount, 6);
+    }
+
+    #[test]
+    fn settle_rewards_pays_fractional_token_rates_in_base_units() {
+        // 1.5 tokens of an 18 decimals mint per node and day
+        let reward_per_node = 1_500_000_000_000_000_000;
+        let mut pool_state = pool(reward_per_node, 2);
+        let mut user_stake_entry = entry(2);
+
+        update_pool(&mut pool_state, None, SECONDS_PER_DAY as i64 / 2).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        assert_eq!(user_stake_entry.claimable_amount, reward_per_node);
+        assert_eq!(reward_liabilities(&pool_state), reward_per_node);
+    }
+
+    #[test]
+    fn sync_reward_debt_only_pays_new_nodes_from_now() {
+        let mut pool_state = pool(1, 1);
+        let mut user_stake_entry = entry(1);
+        let day = SECONDS_PER_DAY as i64;
+
+        update_pool(&mut pool_state, None, day).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+        user_stake_entry.staked_amount = 2;
+        pool_state.total_staked_nodes = 2;
+        sync_reward_debt(&mut user_stake_entry, &pool_state).unwrap();
+
+        update_pool(&mut pool_state, None, 2 * day).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        assert_eq!(user_stake_entry.claimable_amount, 1 + 2);
+    }
+
+    fn pending_update(reward_per_node: u64, eta: i64) -> PendingPoolUpdate {
+        PendingPoolUpdate {
+            tokens_per_node: 7,
+            reward_per_node,
+            max_allocation: 20,
+            treasury_to_selling: 0.5,
+            eta,
+        }
+    }
+
+    #[test]
+    fn apply_pool_update_waits_for_eta() {
+        let mut pool_state = pool(1, 1);
+        let day = SECONDS_PER_DAY as i64;
+
+        assert!(apply_pool_update(&mut pool_state, &pending_update(5, day), None, day - 1).is_err());
+        assert_eq!(pool_state.reward_per_node, 1);
+
+        apply_pool_update(&mut pool_state, &pending_update(5, day), None, day).unwrap();
+
+        assert_eq!(pool_state.tokens_per_node, 7);
+        assert_eq!(pool_state.reward_per_node, 5);
+        assert_eq!(pool_state.max_allocation, 20);
+        assert_eq!(pool_state.treasury_to_selling, 0.5);
+    }
+
+    #[test]
+    fn apply_pool_update_settles_old_rate_first() {
+        let mut pool_state = pool(1, 2);
+        let mut user_stake_entry = entry(2);
+        let day = SECONDS_PER_DAY as i64;
+
+        apply_pool_update(&mut pool_state, &pending_update(5, day), None, day).unwrap();
+        // one base unit per node accrued at the old rate before the switch
+        assert_eq!(pool_state.acc_reward_per_node, ACC_REWARD_PRECISION);
+
+        update_pool(&mut pool_state, None, 2 * day).unwrap();
+        settle_rewards(&mut user_stake_entry, &pool_state).unwrap();
+
+        // a day at the old rate, then a day at the new one
+        assert_eq!(user_stake_entry.claimable_amount, 2 + 2 * 5);
+    }
+}
@@ -0,0 +1,223 @@
+use anchor_lang::prelude::*;
+use crate::account::*;
+use crate::error::ErrorCode;
+
+pub fn is_vesting_enabled(pool_state: &PoolState) -> bool {
+    pool_state.vesting_cliff > 0 || pool_state.vesting_duration > 0
+}
+
+/// Part of the tranche unlocked at `now`: nothing before the cliff, then
+/// linearly from `start_at` until its duration has passed.
+pub fn vested_amount(tranche: &VestingTranche, now: i64) -> Result<u64> {
+    let elapsed = now.saturating_sub(tranche.start_at);
+
+    if elapsed < tranche.cliff {
+        return Ok(0);
+    }
+
+    if elapsed >= tranche.duration {
+        return Ok(tranche.amount);
+    }
+
+    let vested = u128::from(tranche.amount)
+        .checked_mul(elapsed as u128)
+        .ok_or(ErrorCode::UnableCalculatingVesting)?
+        / tranche.duration as u128;
+
+    u64::try_from(vested).map_err(|_| ErrorCode::UnableCalculatingVesting.into())
+}
+
+/// Amount the owner can withdraw at `now`.
+pub fn releasable_amount(reward_vesting: &RewardVesting, now: i64) -> Result<u64> {
+    reward_vesting.tranches.iter().try_fold(reward_vesting.unlocked_amount, |releasable, tranche| {
+        vested_amount(tranche, now)?
+            .checked_sub(tranche.released_amount)
+            .and_then(|value| value.checked_add(releasable))
+            .ok_or(ErrorCode::UnableCalculatingVesting.into())
+    })
+}
+
+/// Amount that is still locked at `now`.
+pub fn locked_amount(reward_vesting: &RewardVesting, now: i64) -> Result<u64> {
+    reward_vesting.tranches.iter().try_fold(0u64, |locked, tranche| {
+        tranche.amount
+            .checked_sub(vested_amount(tranche, now)?)
+            .and_then(|value| value.checked_add(locked))
+            .ok_or(ErrorCode::UnableCalculatingVesting.into())
+    })
+}
+
+/// Adds freshly claimed rewards as a new tranche vesting from `now` with the
+/// pool's current settings, so earlier claims keep their own schedule.
+pub fn lock_rewards(reward_vesting: &mut RewardVesting, pool_state: &PoolState, amount: u64, now: i64) -> Result<()> {
+    collapse_vested(reward_vesting, now)?;
+    require!(reward_vesting.tranches.len() < RewardVesting::MAX_TRANCHES, ErrorCode::TooManyVestingTranches);
+
+    reward_vesting.tranches.push(VestingTranche {
+        amount,
+        released_amount: 0,
+        start_at: now,
+        cliff: pool_state.vesting_cliff,
+        duration: pool_state.vesting_duration,
+    });
+
+    Ok(())
+}
+
+/// Books `amount` as withdrawn, spending unlocked leftovers first and then
+/// the oldest tranches.
+pub fn consume_releasable(reward_vesting: &mut RewardVesting, amount: u64, now: i64) -> Result<()> {
+    let from_unlocked = amount.min(reward_vesting.unlocked_amount);
+    let mut remaining = amount - from_unlocked;
+    reward_vesting.unlocked_amount -= from_unlocked;
+
+    for tranche in reward_vesting.tranches.iter_mut() {
+        if remaining == 0 {
+            break;
+        }
+
+        let available = vested_amount(tranche, now)?.checked_sub(tranche.released_amount).ok_or(ErrorCode::UnableCalculatingVesting)?;
+        let released = remaining.min(available);
+        tranche.released_amount += released;
+        remaining -= released;
+    }
+
+    require!(remaining == 0, ErrorCode::UnableCalculatingVesting);
+
+    collapse_vested(reward_vesting, now)
+}
+
+/// Moves what is left of fully vested tranches to `unlocked_amount` and
+/// frees their slots.
+fn collapse_vested(reward_vesting: &mut RewardVesting, now: i64) -> Result<()> {
+    let mut unlocked_amount = reward_vesting.unlocked_amount;
+
+    for tranche in &reward_vesting.tranches {
+        if vested_amount(tranche, now)? == tranche.amount {
+            unlocked_amount = tranche.amount
+                .checked_sub(tranche.released_amount)
+                .and_then(|value| value.checked_add(unlocked_amount))
+                .ok_or(ErrorCode::UnableCalculatingVesting)?;
+        }
+    }
+
+    reward_vesting.unlocked_amount = unlocked_amount;
+    reward_vesting.tranches.retain(|tranche| vested_amount(tranche, now).is_ok_and(|vested| vested < tranche.amount));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(vesting_cliff: i64, vesting_duration: i64) -> PoolState {
+        let mut pool_state = PoolState::deserialize(&mut &[0u8; PoolState::SPACE][..]).unwrap();
+        pool_state.vesting_cliff = vesting_cliff;
+        pool_state.vesting_duration = vesting_duration;
+        pool_state
+    }
+
+    fn empty_vesting() -> RewardVesting {
+        RewardVesting { unlocked_amount: 0, tranches: Vec::new() }
+    }
+
+    fn tranche(amount: u64, start_at: i64, cliff: i64, duration: i64) -> VestingTranche {
+        VestingTranche { amount, released_amount: 0, start_at, cliff, duration }
+    }
+
+    #[test]
+    fn vested_amount_is_zero_until_the_cliff() {
+        let tranche = tranche(1_000, 100, 10, 100);
+
+        assert_eq!(vested_amount(&tranche, 50).unwrap(), 0);
+        assert_eq!(vested_amount(&tranche, 109).unwrap(), 0);
+        // at the cliff everything since `start_at` unlocks at once
+        assert_eq!(vested_amount(&tranche, 110).unwrap(), 100);
+        assert_eq!(vested_amount(&tranche, 150).unwrap(), 500);
+        assert_eq!(vested_amount(&tranche, 200).unwrap(), 1_000);
+        assert_eq!(vested_amount(&tranche, 10_000).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn cliff_longer_than_duration_unlocks_everything_at_the_cliff() {
+        let tranche = tranche(1_000, 0, 50, 20);
+
+        assert_eq!(vested_amount(&tranche, 49).unwrap(), 0);
+        assert_eq!(vested_amount(&tranche, 50).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn each_claim_vests_on_its_own_schedule() {
+        let mut reward_vesting = empty_vesting();
+        let pool_state = pool(0, 100);
+
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 0).unwrap();
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 50).unwrap();
+
+        // the second claim doesn't restart the first one
+        assert_eq!(releasable_amount(&reward_vesting, 50).unwrap(), 500);
+        assert_eq!(releasable_amount(&reward_vesting, 100).unwrap(), 1_000 + 500);
+        assert_eq!(locked_amount(&reward_vesting, 100).unwrap(), 500);
+    }
+
+    #[test]
+    fn tranches_keep_the_settings_they_were_locked_with() {
+        let mut reward_vesting = empty_vesting();
+        let mut pool_state = pool(0, 100);
+
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 0).unwrap();
+        pool_state.vesting_cliff = 500;
+        pool_state.vesting_duration = 1_000;
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 0).unwrap();
+
+        assert_eq!(releasable_amount(&reward_vesting, 100).unwrap(), 1_000);
+        assert_eq!(locked_amount(&reward_vesting, 100).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn consume_releasable_spends_unlocked_then_oldest_tranches() {
+        let mut reward_vesting = empty_vesting();
+        let pool_state = pool(0, 100);
+
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 0).unwrap();
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 50).unwrap();
+        // the first tranche is fully vested and folded into `unlocked_amount`
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 100).unwrap();
+        assert_eq!(reward_vesting.unlocked_amount, 1_000);
+        assert_eq!(reward_vesting.tranches.len(), 2);
+
+        consume_releasable(&mut reward_vesting, 1_200, 100).unwrap();
+        assert_eq!(reward_vesting.unlocked_amount, 0);
+        assert_eq!(reward_vesting.tranches[0].released_amount, 200);
+        assert_eq!(releasable_amount(&reward_vesting, 100).unwrap(), 300);
+
+        assert!(consume_releasable(&mut reward_vesting, 301, 100).is_err());
+    }
+
+    #[test]
+    fn consume_releasable_drops_fully_released_tranches() {
+        let mut reward_vesting = empty_vesting();
+        let pool_state = pool(0, 100);
+
+        lock_rewards(&mut reward_vesting, &pool_state, 1_000, 0).unwrap();
+        consume_releasable(&mut reward_vesting, 1_000, 100).unwrap();
+
+        assert_eq!(reward_vesting.unlocked_amount, 0);
+        assert!(reward_vesting.tranches.is_empty());
+    }
+
+    #[test]
+    fn lock_rewards_rejects_too_many_tranches() {
+        let mut reward_vesting = empty_vesting();
+        let pool_state = pool(0, 1_000);
+
+        for now in 0..RewardVesting::MAX_TRANCHES as i64 {
+            lock_rewards(&mut reward_vesting, &pool_state, 1, now).unwrap();
+        }
+
+        assert!(lock_rewards(&mut reward_vesting, &pool_state, 1, 20).is_err());
+        // once older tranches are vested their slots free up again
+        assert!(lock_rewards(&mut reward_vesting, &pool_state, 1, 1_000).is_ok());
+    }
+}
@@ -8,10 +8,17 @@ import {
 } from "@coral-xyz/anchor";
 import {
   getAssociatedTokenAddress,
-  getAccount
+  getAccount,
+  getMint,
+  createMint,
+  createAssociatedTokenAccount,
+  mintTo,
+  TOKEN_PROGRAM_ID
 } from "@solana/spl-token";
 import { readFileSync } from "fs";
 import { bs58 } from "@coral-xyz/anchor/dist/cjs/utils/bytes";
+import { expect } from "chai";
+import { keccak_256 } from "@noble/hashes/sha3";
 import { NodeStaking } from "../target/types/node_staking";
 
 describe("node-staking", () => {
@@ -31,45 +38,172 @@ describe("node-staking", () => {
 
   const TOKEN_METADATA_PROGRAM_ID = new web3.PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
-  const [mint] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("mint")],
+  // accounts only needed to buy or stake nodes as NFTs
+  const noNodeNfts = {
+    nodeCollection: null,
+    collectionMint: null,
+    collectionMetadata: null,
+    collectionMasterEdition: null,
+    associatedTokenProgram: null,
+    tokenMetadataProgram: null
+  };
+
+  const poolId = 0;
+
+  const [poolState] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_state"), new BN(poolId).toArrayLike(Buffer, "le", 2)],
     program.programId
   )
 
-  const [poolState] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("pool_state")],
+  // second pool, selling the token minted by the first one
+  const sharedPoolId = 1;
+
+  const [sharedPoolState] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_state"), new BN(sharedPoolId).toArrayLike(Buffer, "le", 2)],
+    program.programId
+  )
+
+  const [mint] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("mint"), poolState.toBuffer()],
     program.programId
   )
 
   const [presaleState] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("presale_state")],
+    [Buffer.from("presale_state"), poolState.toBuffer(), Buffer.from([0])],
     program.programId
   )
 
   const [presaleVault] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("presale_vault")],
+    [Buffer.from("presale_vault"), poolState.toBuffer()],
     program.programId
   )
 
   const [userStakeEntry] = web3.PublicKey.findProgramAddressSync(
-    [provider.publicKey.toBuffer()],
+    [poolState.toBuffer(), provider.publicKey.toBuffer()],
+    program.programId
+  )
+
+  const [rewardVault] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("reward_vault"), poolState.toBuffer()],
     program.programId
   )
 
   const vaultAuthKey = new web3.PublicKey("6JvsMVc9rwY9AG63qsqrfoDcNPgRmx9JfMHMHaX7TRoS");
 
+  const [multisig] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("multisig")],
+    program.programId
+  )
+
+  // proposals expire an hour after they are created
+  const createProposal = async (action, pool = poolState) => {
+    const multisigData = await program.account.multisig.fetch(multisig)
+    const [proposal] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal"), multisigData.proposalCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )
+    const expiresAt = new BN(Math.floor(new Date().getTime() / 1000) + 60 * 60)
+
+    await program.methods.createProposal(pool, action, expiresAt)
+      .accounts({
+        proposal
+      })
+      .rpc()
+
+    return proposal
+  }
+
+  const presaleStateOf = (roundId: number, pool = poolState) => web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("presale_state"), pool.toBuffer(), Buffer.from([roundId])],
+    program.programId
+  )[0]
+
+  const presalePurchaseOf = (roundId: number, pool = poolState, user = provider.publicKey) => web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("presale_purchase"), pool.toBuffer(), Buffer.from([roundId]), user.toBuffer()],
+    program.programId
+  )[0]
+
+  // presale whitelist leaves are keccak256(wallet || allocation as little endian u16),
+  // pairs are hashed in sorted order
+  const allocationLeaf = (wallet: web3.PublicKey, allocation: number) => Buffer.from(
+    keccak_256(Buffer.concat([wallet.toBuffer(), new BN(allocation).toArrayLike(Buffer, "le", 2)]))
+  )
+
+  const hashPair = (a: Buffer, b: Buffer) => Buffer.from(
+    keccak_256(Buffer.compare(a, b) <= 0 ? Buffer.concat([a, b]) : Buffer.concat([b, a]))
+  )
+
+  // buys presale nodes through the provider's referral
+  const referredBuyer = web3.Keypair.generate()
+  const referredAllocation = 20;
+
+  const whitelistAllocation = 10;
+  const otherLeaf = allocationLeaf(referredBuyer.publicKey, referredAllocation)
+  const whitelistRoot = hashPair(allocationLeaf(provider.publicKey, whitelistAllocation), otherLeaf)
+  const whitelistProof = [Array.from(otherLeaf)]
+  const referredProof = [Array.from(allocationLeaf(provider.publicKey, whitelistAllocation))]
+
+  const [referralAccount] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("referral"), poolState.toBuffer(), provider.publicKey.toBuffer()],
+    program.programId
+  )
+
+  // Pyth-format SOL/USD account at $150 loaded from tests/fixtures/sol_usd_price.json
+  const priceFeed = new web3.PublicKey("6hiE8E9xXK1QoggWKuReMsU3oXidoYeEdKdCTDiA6vjY")
+
+  // unix timestamp of the validator clock, which is what the program compares against
+  const clockNow = async () => {
+    const clock = await provider.connection.getAccountInfo(web3.SYSVAR_CLOCK_PUBKEY)
+    return new BN(clock.data.subarray(32, 40), "le").toNumber()
+  }
+
+  const waitUntil = async (timestamp: number) => {
+    while (await clockNow() <= timestamp) {
+      await new Promise((resolve) => setTimeout(resolve, 1000))
+    }
+  }
+
+  const tokenBalance = async (tokenAccount: web3.PublicKey) => new BN((await getAccount(provider.connection, tokenAccount)).amount.toString())
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    let error
+    try {
+      await promise
+    } catch (err) {
+      error = err
+    }
+    expect(error, `expected ${code}`).to.exist
+    expect(error.error?.errorCode?.code ?? error.toString()).to.contain(code)
+  }
+
+  it("Config is initialized!", async () => {
+    const [programData] = web3.PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    )
+
+    const tx = await program.methods.initializeConfig(vaultAuthKey, provider.publicKey, provider.publicKey, provider.publicKey)
+      .accounts({
+        programData
+      })
+      .rpc();
+
+    console.log("initialize config tx", tx);
+  });
+
   it("Pool State is initialized!", async () => {
-    const tokensPerNode = new BN(10);
-    const rewardsPerNode = 20;
+    // the mint has 18 decimals, all amounts including the daily reward per node are in base units
+    const tokensPerNode = new BN("1000000000000000");
+    const rewardsPerNode = new BN("10000000000000000000");
     const maxAllocation = 1000;
     const treasury_to_selling = 0.2;
-    const treasuryVault = await getAssociatedTokenAddress(
-      mint,
-      provider.publicKey
-    )
-    const tx = await program?.methods.initializePool(tokensPerNode, rewardsPerNode, maxAllocation, treasury_to_selling)
+    const unstakeCooldown = new BN(5);
+    const unstakeRefundRate = 5000;
+    const maxSupply = new BN("10000000000000000000");
+    const tx = await program?.methods.initializePool(poolId, tokensPerNode, rewardsPerNode, maxAllocation, treasury_to_selling, unstakeCooldown, unstakeRefundRate, maxSupply)
       .accounts({
-        treasuryVault
+        mint,
+        sharedPool: null
       })
       .rpc();
     console.log("Your transaction signature", tx);
@@ -81,30 +215,103 @@ describe("node-staking", () => {
       poolState,
       true
     )
+    const treasuryVault = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
     const tx = await program?.methods.initializeSellingVault()
       .accounts({
-        sellingVault
+        poolState,
+        mint,
+        sellingVault,
+        treasury: provider.publicKey,
+        treasuryVault
+      })
+      .rpc();
+    console.log("Your transaction signature", tx);
+  });
+
+  it("Token is initialized!", async () => {
+    const [metadata] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer()
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    )
+    const name = "Solana Node Staking Token"
+    const symbol = "NST"
+    const uri = "https://ipfs.io/ipfs/QmRtzvCek4tv3u9r1zjEm3wZbuT8MQtaaoAGonZK1CATkB"
+    const tx = await program?.methods.initializeToken(name, symbol, uri)
+      .accounts({
+        poolState,
+        metadata,
+        mint
       })
       .rpc();
     console.log("Your transaction signature", tx);
   });
 
-  // it("Token is initialized!", async () => {
-  //   const [metadata] = web3.PublicKey.findProgramAddressSync(
-  //     [
-  //       Buffer.from("metadata"),
-  //       TOKEN_METADATA_PROGRAM_ID.toBuffer(),
-  //       mint.toBuffer()
-  //     ],
-  //     TOKEN_METADATA_PROGRAM_ID
-  //   )
-  //   const tx = await program?.methods.initializeToken()
-  //     .accounts({
-  //       metadata
-  //     })
-  //     .rpc();
-  //   console.log("Your transaction signature", tx);
-  // });
+  it("Token metadata is updated!", async () => {
+    const [metadata] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer()
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    )
+    // metadata strings are padded to their max length, the name follows the key, update authority and mint
+    const metadataName = async () => {
+      const metadataAcc = await provider.connection.getAccountInfo(metadata)
+      const nameLen = metadataAcc.data.readUInt32LE(65)
+      return metadataAcc.data.subarray(69, 69 + nameLen).toString().replace(/\0/g, "")
+    }
+    const symbol = "NST"
+    const uri = "https://ipfs.io/ipfs/QmRtzvCek4tv3u9r1zjEm3wZbuT8MQtaaoAGonZK1CATkB"
+
+    const tx = await program.methods.updateTokenMetadata("Node Staking Token", symbol, uri, false)
+      .accounts({
+        poolState,
+        mint
+      })
+      .rpc()
+
+    expect(await metadataName()).to.equal("Node Staking Token")
+
+    await program.methods.updateTokenMetadata("Node Staking Token", symbol, uri, true)
+      .accounts({
+        poolState,
+        mint
+      })
+      .rpc()
+
+    // locked metadata can't change anymore
+    let error
+    try {
+      await program.methods.updateTokenMetadata("Renamed Token", symbol, uri, false)
+        .accounts({
+          poolState,
+          mint
+        })
+        .rpc()
+    } catch (err) {
+      error = err
+    }
+    expect(error).to.exist
+    expect(await metadataName()).to.equal("Node Staking Token")
+
+    console.log("update token metadata tx", tx)
+  });
+
+  it("Multisig is initialized!", async () => {
+    const threshold = 1;
+
+    const tx = await program.methods.initializeMultisig([provider.publicKey], threshold).rpc();
+
+    console.log("initialize multisig tx", tx);
+  });
 
   it("Token Mint!", async () => {
     // const sellingVault = await getAssociatedTokenAddress(
@@ -112,7 +319,7 @@ describe("node-staking", () => {
     //   poolState,
     //   true
     // )
-    const amount = new BN("50000000000")
+    const amount = new BN("6000000000000000000")
     const treasuryVault = await getAssociatedTokenAddress(
       mint,
       provider.publicKey
@@ -122,59 +329,241 @@ describe("node-staking", () => {
       poolState,
       true
     )
+    const proposal = await createProposal({ mintTokens: { amount } })
+
     const tx = await program?.methods.mintTokens(amount)
       .accounts({
+        poolState,
+        mintPool: null,
         treasuryVault,
-        sellingVault
+        sellingVault,
+        proposal
       })
       .rpc();
+
+    const poolStateData = await program.account.poolState.fetch(poolState)
+    const treasuryAcc = await getAccount(provider.connection, treasuryVault)
+    const sellingAcc = await getAccount(provider.connection, sellingVault)
+    expect(poolStateData.mintedToTreasury.add(poolStateData.mintedToSelling).toString()).to.equal(amount.toString())
+    expect(treasuryAcc.amount.toString()).to.equal(poolStateData.mintedToTreasury.toString())
+    expect(sellingAcc.amount.toString()).to.equal(poolStateData.mintedToSelling.toString())
+
     console.log("Your transaction signature", tx);
   });
 
+  it("Minting past the max supply fails", async () => {
+    const poolStateData = await program.account.poolState.fetch(poolState)
+    const amount = poolStateData.maxSupply
+      .sub(poolStateData.mintedToTreasury)
+      .sub(poolStateData.mintedToSelling)
+      .sub(poolStateData.mintedToRewards)
+      .addn(1)
+    const treasuryVault = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const sellingVault = await getAssociatedTokenAddress(
+      mint,
+      poolState,
+      true
+    )
+    const proposal = await createProposal({ mintTokens: { amount } })
+
+    await expectError(
+      program.methods.mintTokens(amount)
+        .accounts({
+          poolState,
+          mintPool: null,
+          treasuryVault,
+          sellingVault,
+          proposal
+        })
+        .rpc(),
+      "SupplyCapExceeded"
+    )
+  });
+
+  it("Mint Nodes", async () => {
+    const amount = 20000;
+    const proposal = await createProposal({ mintNodes: { amount } })
+
+    const tx = await program.methods.mintNodes(amount)
+      .accounts({
+        poolState,
+        proposal
+      })
+      .rpc()
+
+    console.log("mint nodes transaction", tx)
+  })
+
   it("Initilalize Presale", async () => {
     const pricePerNode = new BN(1);
     const maxAllocation = 1000;
-    const presaleStartAt = new BN(Math.floor(new Date().getTime() / 1000))
-    const presaleEndAt = new BN(Math.floor(new Date('2024.5.30').getTime() / 1000))
+    // short round, the staking tests wait for it to end
+    const presaleStartAt = new BN(await clockNow())
+    const presaleEndAt = presaleStartAt.add(new BN(20))
     const totalPresaleAmount = 10000;
+    const softCap = new BN(10);
 
     const tx = await program.methods.initializePresale(
       pricePerNode,
       maxAllocation,
       presaleStartAt,
       presaleEndAt,
-      totalPresaleAmount
-    ).rpc()
+      totalPresaleAmount,
+      softCap
+    ).accounts({ poolState }).rpc()
 
     console.log("initialize presale tx", tx)
   })
 
-  it("Mint Nodes", async () => {
-    const amount = 1000;
+  it("Configure referral", async () => {
+    const referralBps = 500;
 
-    const tx = await program.methods.mintNodes(amount).rpc()
+    const tx = await program.methods.configureReferral(referralBps).accounts({ poolState }).rpc()
 
-    console.log("mint nodes transaction", tx)
+    console.log("configure referral tx", tx)
+  })
+
+  it("Initialize referral", async () => {
+    const tx = await program.methods.initializeReferral().accounts({ poolState }).rpc()
+
+    const referralData = await program.account.referralAccount.fetch(referralAccount)
+
+    console.log("referrer", referralData.referrer.toBase58())
+
+    console.log("initialize referral tx", tx)
   })
 
   it("Initialize User Stake Entry", async () => {
-    const tx = await program.methods.initializeUserStake().rpc();
+    const tx = await program.methods.initializeUserStake().accounts({ poolState }).rpc();
 
     console.log("Initialize user stake entry tx", tx)
   })
 
+  it("Whitelist presale round", async () => {
+    const tx = await program.methods.setPresaleWhitelist(Array.from(whitelistRoot))
+      .accounts({
+        poolState,
+        presaleState
+      })
+      .rpc()
+
+    const presaleStateData = await program.account.presaleState.fetch(presaleState)
+    expect(Buffer.from(presaleStateData.merkleRoot).equals(whitelistRoot)).to.be.true
+
+    const sell = (amount: number, allocation: number) => program.methods.sellNodesAtPresale(amount, allocation, whitelistProof, new BN(1), 0, false)
+      .accounts({
+        poolState,
+        presaleState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        referralAccount: null,
+        referralPresale: null,
+        priceFeed: null,
+        tokenProgram: null
+      })
+      .rpc()
+
+    // the proof only holds for the allocation it was built with
+    await expectError(sell(1, whitelistAllocation + 1), "InvalidWhitelistProof")
+    await expectError(sell(whitelistAllocation + 1, whitelistAllocation), "WhitelistAllocationOverflow")
+
+    console.log("set presale whitelist tx", tx)
+  })
+
   it("Selling Nodes At Presale", async () => {
     const amount = 10;
 
-    const tx = await program.methods.sellNodesAtPresale(amount)
+    const expectedPricePerNode = new BN(1);
+    const maxSlippageBps = 0;
+
+    const tx = await program.methods.sellNodesAtPresale(amount, whitelistAllocation, whitelistProof, expectedPricePerNode, maxSlippageBps, false)
       .accounts({
-        presaleState
+        poolState,
+        presaleState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        referralAccount: null,
+        referralPresale: null,
+        priceFeed: null,
+        tokenProgram: null
       })
       .rpc();
 
+    // bought nodes wait in the purchase until the round is known to have succeeded
+    const purchaseData = await program.account.presalePurchase.fetch(presalePurchaseOf(0))
+    const poolStateData = await program.account.poolState.fetch(poolState)
+    expect(purchaseData.nodes).to.equal(amount)
+    expect(poolStateData.totalStakedNodes).to.equal(0)
+
     console.log("Selling Nodes At Presale", tx)
   })
 
+  it("Selling Nodes At Presale with a referral", async () => {
+    const airdropSig = await provider.connection.requestAirdrop(referredBuyer.publicKey, web3.LAMPORTS_PER_SOL)
+    const latestBlockHash = await provider.connection.getLatestBlockhash()
+    await provider.connection.confirmTransaction({
+      blockhash: latestBlockHash.blockhash,
+      lastValidBlockHeight: latestBlockHash.lastValidBlockHeight,
+      signature: airdropSig
+    })
+
+    const tx = await program.methods.sellNodesAtPresale(referredAllocation, referredAllocation, referredProof, new BN(1), 0, false)
+      .accounts({
+        poolState,
+        presaleState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        referralAccount,
+        referralPresale: null,
+        priceFeed: null,
+        user: referredBuyer.publicKey,
+        tokenProgram: null
+      })
+      .signers([referredBuyer])
+      .rpc()
+
+    // 5% of 20 lamports, locked until the round is known to have succeeded
+    const referralData = await program.account.referralAccount.fetch(referralAccount)
+    const presaleStateData = await program.account.presaleState.fetch(presaleState)
+    expect(referralData.lockedLamports.toNumber()).to.equal(1)
+    expect(referralData.lockedRound).to.equal(0)
+    expect(referralData.referredNodes).to.equal(referredAllocation)
+    expect(presaleStateData.referralCommission.toNumber()).to.equal(1)
+
+    console.log("referred presale tx", tx)
+  })
+
+  it("stake presale nodes once the round succeeded", async () => {
+    const presaleStateData = await program.account.presaleState.fetch(presaleState)
+
+    await expectError(
+      program.methods.stakePresaleNodes(0).accounts({ poolState, emissionSchedule: null, ...noNodeNfts }).rpc(),
+      "NotEndedPresale"
+    )
+
+    await waitUntil(presaleStateData.presaleEndAt.toNumber())
+
+    const tx = await program.methods.stakePresaleNodes(0).accounts({ poolState, emissionSchedule: null, ...noNodeNfts }).rpc()
+
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const poolStateData = await program.account.poolState.fetch(poolState)
+    const purchaseData = await program.account.presalePurchase.fetch(presalePurchaseOf(0))
+    expect(userStakeEntryData.stakedAmount).to.equal(10)
+    expect(userStakeEntryData.presaleNodes).to.equal(10)
+    expect(poolStateData.totalStakedNodes).to.equal(10)
+    expect(purchaseData.isStaked).to.be.true
+
+    await expectError(
+      program.methods.stakePresaleNodes(0).accounts({ poolState, emissionSchedule: null, ...noNodeNfts }).rpc(),
+      "NothingToStake"
+    )
+
+    console.log("stake presale nodes tx", tx)
+  })
+
   it("withdraw cap", async () => {
     const poolAuthKeypair = web3.Keypair.fromSecretKey(
       bs58.decode(
@@ -195,8 +584,13 @@ describe("node-staking", () => {
       signature: airdropSig
     })  
 
-    const tx = await program.methods.withdrawCap()
+    const proposal = await createProposal({ withdrawCap: { roundId: 0 } })
+
+    const tx = await program.methods.withdrawCap(0)
       .accounts({
+        poolState,
+        presaleState,
+        proposal,
         withdrawer: poolAuthKeypair.publicKey
       })
       .signers([poolAuthKeypair])
@@ -209,6 +603,208 @@ describe("node-staking", () => {
     )
   })
 
+  it("Finalize presale", async () => {
+    const poolNodesBefore = (await program.account.poolState.fetch(poolState)).totalNodes
+    const finalize = (burnUnsold: boolean) => program.methods.finalizePresale(0, burnUnsold)
+      .accounts({
+        poolState,
+        presaleState
+      })
+      .rpc()
+
+    const tx = await finalize(false)
+
+    // unsold nodes go back to the pool
+    const presaleStateData = await program.account.presaleState.fetch(presaleState)
+    expect(presaleStateData.isFinalized).to.be.true
+    expect(presaleStateData.soldAmount).to.equal(whitelistAllocation + referredAllocation)
+    expect((await program.account.poolState.fetch(poolState)).totalNodes)
+      .to.equal(poolNodesBefore + presaleStateData.totalPresaleAmount - presaleStateData.soldAmount)
+
+    await expectError(finalize(true), "AlreadyFinalized")
+
+    console.log("presale sold nodes", presaleStateData.soldAmount)
+
+    console.log("finalize presale tx", tx)
+  })
+
+  it("claim referral commission of a successful round", async () => {
+    const vaultBalanceBefore = await provider.connection.getBalance(presaleVault)
+    const referrerTokenAccount = await getAssociatedTokenAddress(mint, provider.publicKey)
+
+    const tx = await program.methods.claimReferralRewards()
+      .accounts({
+        poolState,
+        referralPresale: presaleState,
+        sellingMint: mint,
+        referrerTokenAccount
+      })
+      .rpc()
+
+    const referralData = await program.account.referralAccount.fetch(referralAccount)
+    expect(vaultBalanceBefore - await provider.connection.getBalance(presaleVault)).to.equal(1)
+    expect(referralData.lockedLamports.toNumber()).to.equal(0)
+    expect(referralData.pendingLamports.toNumber()).to.equal(0)
+
+    console.log("claim referral rewards tx", tx)
+  })
+
+  it("Open next presale round", async () => {
+    const pricePerNode = new BN(2);
+    const maxAllocation = 500;
+    // starts a little later, usd pricing can only be configured before that
+    const presaleStartAt = new BN(await clockNow() + 5)
+    const presaleEndAt = presaleStartAt.add(new BN(15))
+    const totalPresaleAmount = 5000;
+    // out of reach for the test purchase, so the round fails and is refunded
+    const softCap = new BN(web3.LAMPORTS_PER_SOL);
+
+    const tx = await program.methods.openPresaleRound(
+      pricePerNode,
+      maxAllocation,
+      presaleStartAt,
+      presaleEndAt,
+      totalPresaleAmount,
+      softCap
+    ).accounts({ poolState }).rpc()
+
+    const poolStateData = await program.account.poolState.fetch(poolState)
+
+    console.log("current presale round", poolStateData.currentRound)
+
+    console.log("open presale round tx", tx)
+  })
+
+  it("Price next presale round in USD", async () => {
+    const usdPricePerNode = new BN(1_000_000);
+    const maxPriceAge = new BN(4_000_000_000);
+    const maxConfidenceBps = 100;
+
+    const tx = await program.methods.configureUsdPricing(usdPricePerNode, maxPriceAge, maxConfidenceBps)
+      .accounts({
+        poolState,
+        presaleState: presaleStateOf(1),
+        priceFeed
+      })
+      .rpc()
+
+    const presaleStateData = await program.account.presaleState.fetch(presaleStateOf(1))
+    expect(presaleStateData.usdPricePerNode.toNumber()).to.equal(1_000_000)
+    expect(presaleStateData.priceFeed.toBase58()).to.equal(priceFeed.toBase58())
+
+    console.log("configure usd pricing tx", tx)
+  })
+
+  it("Extend presale round", async () => {
+    const [nextPresaleState] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("presale_state"), poolState.toBuffer(), Buffer.from([1])],
+      program.programId
+    )
+    const presaleStateData = await program.account.presaleState.fetch(nextPresaleState)
+
+    const tx = await program.methods.updatePresale(
+      presaleStateData.pricePerNode,
+      presaleStateData.maxAllocation,
+      presaleStateData.presaleStartAt,
+      presaleStateData.presaleEndAt.add(new BN(5))
+    ).accounts({ poolState, previousPresale: presaleState }).rpc()
+
+    console.log("update presale tx", tx)
+  })
+
+  it("refund a round that missed its soft cap", async () => {
+    const amount = 5;
+    const roundPresaleState = presaleStateOf(1)
+    // $1 per node at $150 per SOL
+    const lamportsPerNode = 6_666_666;
+
+    let presaleStateData = await program.account.presaleState.fetch(roundPresaleState)
+    await waitUntil(presaleStateData.presaleStartAt.toNumber())
+
+    const sell = (expectedPricePerNode: number, feed: web3.PublicKey | null) => program.methods.sellNodesAtPresale(amount, 0, [], new BN(expectedPricePerNode), 0, false)
+      .accounts({
+        poolState,
+        presaleState: roundPresaleState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        referralAccount: null,
+        referralPresale: null,
+        priceFeed: feed,
+        tokenProgram: null
+      })
+      .rpc()
+
+    await expectError(sell(lamportsPerNode, null), "InvalidPriceFeed")
+    await expectError(sell(lamportsPerNode - 1, priceFeed), "SlippageExceeded")
+    await sell(lamportsPerNode, priceFeed)
+
+    presaleStateData = await program.account.presaleState.fetch(roundPresaleState)
+    expect(presaleStateData.raisedAmount.toNumber()).to.equal(amount * lamportsPerNode)
+
+    // the referrer's commission on this round is forfeit once it fails
+    await program.methods.sellNodesAtPresale(1, 0, [], new BN(lamportsPerNode), 0, false)
+      .accounts({
+        poolState,
+        presaleState: roundPresaleState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        referralAccount,
+        referralPresale: null,
+        priceFeed,
+        user: referredBuyer.publicKey,
+        tokenProgram: null
+      })
+      .signers([referredBuyer])
+      .rpc()
+    expect((await program.account.referralAccount.fetch(referralAccount)).lockedLamports.toNumber())
+      .to.equal(Math.floor(lamportsPerNode * 500 / 10000))
+
+    await waitUntil(presaleStateData.presaleEndAt.toNumber())
+
+    await expectError(
+      program.methods.claimReferralRewards()
+        .accounts({
+          poolState,
+          referralPresale: roundPresaleState,
+          sellingMint: mint,
+          referrerTokenAccount: await getAssociatedTokenAddress(mint, provider.publicKey)
+        })
+        .rpc(),
+      "NothingToClaim"
+    )
+    expect((await program.account.referralAccount.fetch(referralAccount)).pendingLamports.toNumber()).to.equal(0)
+
+    await expectError(
+      program.methods.stakePresaleNodes(1).accounts({ poolState, emissionSchedule: null, ...noNodeNfts }).rpc(),
+      "SoftCapNotReached"
+    )
+
+    const poolNodesBefore = (await program.account.poolState.fetch(poolState)).totalNodes
+    const vaultBalanceBefore = await provider.connection.getBalance(presaleVault)
+
+    const tx = await program.methods.refundPresale(1)
+      .accounts({
+        poolState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        tokenProgram: null
+      })
+      .rpc()
+
+    const purchaseData = await program.account.presalePurchase.fetch(presalePurchaseOf(1))
+    const poolStateData = await program.account.poolState.fetch(poolState)
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(vaultBalanceBefore - await provider.connection.getBalance(presaleVault)).to.equal(amount * lamportsPerNode)
+    expect(purchaseData.nodes).to.equal(0)
+    expect(purchaseData.paidAmount.toNumber()).to.equal(0)
+    expect(poolStateData.totalNodes).to.equal(poolNodesBefore + amount)
+    // refunded nodes were never staked, so nothing to take back from the entry
+    expect(userStakeEntryData.stakedAmount).to.equal(10)
+    expect(poolStateData.totalStakedNodes).to.equal(10)
+
+    console.log("refund presale tx", tx)
+  })
+
   it("create nodes!", async () => {
     const amount = 100;
     const userTokenAccount = await getAssociatedTokenAddress(
@@ -224,26 +820,883 @@ describe("node-staking", () => {
       poolState,
       true
     )
-    const tx = await program.methods.createNodes(amount)
+    const tx = await program.methods.createNodes(amount, false)
       .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
         userTokenAccount,
         treasuryVault,
-        sellingVault
+        sellingVault,
+        referralAccount: null,
+        ...noNodeNfts
       })
       .rpc()
 
-    const [poolStateAddr] = web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("pool_state")],
-      program.programId
-    )
-
-    const poolStateData = await program.account.poolState.fetch(poolStateAddr)
+    const poolStateData = await program.account.poolState.fetch(poolState)
 
     console.log("nodes balance", poolStateData.totalNodes)
 
     console.log("create nodes transaction", tx)
   })
 
+  it("Reward vault is initialized!", async () => {
+    const tx = await program.methods.initializeRewardVault().accounts({ poolState, mint }).rpc();
+
+    console.log("initialize reward vault tx", tx)
+  })
+
+  it("fund rewards!", async () => {
+    const amount = new BN("500000000000000000")
+    const funderTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const funderBefore = await tokenBalance(funderTokenAccount)
+    const tx = await program.methods.fundRewards(amount)
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        funderTokenAccount
+      })
+      .rpc()
+
+    const rewardVaultBalance = await tokenBalance(rewardVault)
+    expect(rewardVaultBalance.toString()).to.equal(amount.toString())
+    expect(funderBefore.sub(await tokenBalance(funderTokenAccount)).toString()).to.equal(amount.toString())
+
+    console.log("reward vault balance", rewardVaultBalance.toString())
+
+    console.log("fund rewards transaction", tx)
+  })
+
+  it("compound rewards!", async () => {
+    const treasuryVault = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const sellingVault = await getAssociatedTokenAddress(
+      mint,
+      poolState,
+      true
+    )
+    const stakedBefore = (await program.account.userStakeEntry.fetch(userStakeEntry)).stakedAmount
+    const { tokensPerNode, totalNodes } = await program.account.poolState.fetch(poolState)
+    const rewardBefore = await tokenBalance(rewardVault)
+    const treasuryBefore = await tokenBalance(treasuryVault)
+    const sellingBefore = await tokenBalance(sellingVault)
+
+    const tx = await program.methods.compoundRewards()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        treasuryVault,
+        sellingVault
+      })
+      .rpc()
+
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const compounded = userStakeEntryData.stakedAmount - stakedBefore
+    expect(compounded).to.be.greaterThan(0)
+    expect((await program.account.poolState.fetch(poolState)).totalNodes).to.equal(totalNodes - compounded)
+
+    const spent = rewardBefore.sub(await tokenBalance(rewardVault))
+    const toTreasury = (await tokenBalance(treasuryVault)).sub(treasuryBefore)
+    const toSelling = (await tokenBalance(sellingVault)).sub(sellingBefore)
+    expect(spent.toString()).to.equal(tokensPerNode.muln(compounded).toString())
+    expect(toTreasury.add(toSelling).toString()).to.equal(spent.toString())
+
+    console.log("nodes after compound", userStakeEntryData.stakedAmount)
+
+    console.log("compound rewards transaction", tx)
+  })
+
+  it("claim nodes!", async () => {
+    const userTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const vaultBefore = await tokenBalance(rewardVault)
+    const userBefore = await tokenBalance(userTokenAccount)
+
+    const tx = await program.methods.claimNodes()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        userTokenAccount
+      })
+      .rpc()
+
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const vaultAfter = await tokenBalance(rewardVault)
+    const claimed = vaultBefore.sub(vaultAfter)
+    expect(claimed.gtn(0)).to.be.true
+    expect((await tokenBalance(userTokenAccount)).sub(userBefore).toString()).to.equal(claimed.toString())
+    // an underfunded vault pays what it holds and the rest stays claimable
+    expect(userStakeEntryData.claimableAmount.isZero() || vaultAfter.isZero()).to.be.true
+
+    console.log("claimable amount after claim", userStakeEntryData.claimableAmount.toString())
+
+    console.log("claim nodes transaction", tx)
+  })
+
+  it("unstake nodes!", async () => {
+    const amount = 10;
+    const stakedBefore = (await program.account.userStakeEntry.fetch(userStakeEntry)).stakedAmount
+    const tx = await program.methods.unstakeNodes(amount).accounts({ poolState, emissionSchedule: null }).rpc()
+
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(userStakeEntryData.stakedAmount).to.equal(stakedBefore - amount)
+    expect(userStakeEntryData.unstakingAmount).to.equal(amount)
+    // tokens_per_node * amount at the 50% refund rate
+    expect(userStakeEntryData.unstakingRefund.toString()).to.equal("5000000000000000")
+
+    console.log("unstaking nodes", userStakeEntryData.unstakingAmount)
+
+    console.log("unstake nodes transaction", tx)
+  })
+
+  it("withdraw unstaked!", async () => {
+    const userTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const sellingVault = await getAssociatedTokenAddress(
+      mint,
+      poolState,
+      true
+    )
+    const withdraw = () => program.methods.withdrawUnstaked()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        userTokenAccount,
+        sellingVault
+      })
+      .rpc()
+
+    await expectError(withdraw(), "UnstakeCooldownActive")
+
+    const { unstakeRequestedAt, unstakingRefund } = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const poolNodesBefore = (await program.account.poolState.fetch(poolState)).totalNodes
+    const userBefore = await tokenBalance(userTokenAccount)
+    const vaultBefore = await tokenBalance(sellingVault)
+    await waitUntil(unstakeRequestedAt.toNumber() + 5)
+
+    const tx = await withdraw()
+
+    const userStakeEntryData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(userStakeEntryData.unstakingAmount).to.equal(0)
+    expect(userStakeEntryData.unstakingRefund.isZero()).to.be.true
+    expect((await tokenBalance(userTokenAccount)).sub(userBefore).toString()).to.equal(unstakingRefund.toString())
+    expect(vaultBefore.sub(await tokenBalance(sellingVault)).toString()).to.equal(unstakingRefund.toString())
+    expect((await program.account.poolState.fetch(poolState)).totalNodes).to.equal(poolNodesBefore + 10)
+
+    console.log("withdraw unstaked transaction", tx)
+  })
+
+  it("transfer nodes!", async () => {
+    const amount = 5;
+    const recipient = web3.Keypair.generate();
+    const transfer = (nodes: number, to: web3.PublicKey) => program.methods.transferNodes(nodes)
+      .accounts({
+        poolState,
+        emissionSchedule: null,
+        recipient: to
+      })
+      .rpc()
+
+    await expectError(transfer(amount, provider.publicKey), "InvalidTransferAmount")
+
+    const stakedBefore = (await program.account.userStakeEntry.fetch(userStakeEntry)).stakedAmount
+    await expectError(transfer(stakedBefore + 1, recipient.publicKey), "InvalidTransferAmount")
+
+    const tx = await transfer(amount, recipient.publicKey)
+
+    const [recipientStakeEntry] = web3.PublicKey.findProgramAddressSync(
+      [poolState.toBuffer(), recipient.publicKey.toBuffer()],
+      program.programId
+    )
+    const recipientStakeEntryData = await program.account.userStakeEntry.fetch(recipientStakeEntry)
+    expect(recipientStakeEntryData.stakedAmount).to.equal(amount)
+    expect((await program.account.userStakeEntry.fetch(userStakeEntry)).stakedAmount).to.equal(stakedBefore - amount)
+
+    console.log("recipient nodes", recipientStakeEntryData.stakedAmount)
+
+    console.log("transfer nodes transaction", tx)
+  })
+
+  it("queue and cancel pool update!", async () => {
+    const tokensPerNode = new BN(20);
+    const rewardsPerNode = new BN(10);
+    const maxAllocation = 500;
+    const treasuryToSelling = 0.25;
+    const eta = new BN(Math.floor(new Date().getTime() / 1000) + 3 * 24 * 60 * 60)
+
+    const queue = (at: BN) => program.methods.queuePoolUpdate(tokensPerNode, rewardsPerNode, maxAllocation, treasuryToSelling, at).accounts({ poolState }).rpc()
+
+    const queueTx = await queue(eta)
+
+    // one update at a time, and only once its timelock is over
+    await expectError(queue(eta.addn(1)), "PoolUpdateAlreadyQueued")
+    await expectError(
+      program.methods.executePoolUpdate().accounts({ poolState, emissionSchedule: null }).rpc(),
+      "PoolUpdateLocked"
+    )
+
+    console.log("queue pool update tx", queueTx)
+
+    const cancelTx = await program.methods.cancelPoolUpdate().accounts({ poolState }).rpc()
+
+    console.log("cancel pool update tx", cancelTx)
+  })
+
+  it("pause and unpause!", async () => {
+    const pauseAll = (1 << 7) - 1;
+
+    await expectError(program.methods.setPaused(1 << 7).rpc(), "InvalidPauseFlags")
+
+    const pauseTx = await program.methods.setPaused(pauseAll).rpc()
+
+    const userTokenAccount = await getAssociatedTokenAddress(mint, provider.publicKey)
+    await expectError(
+      program.methods.createNodes(1, false)
+        .accounts({
+          poolState,
+          sellingMint: mint,
+          emissionSchedule: null,
+          userTokenAccount,
+          treasuryVault: userTokenAccount,
+          sellingVault: await getAssociatedTokenAddress(mint, poolState, true),
+          referralAccount: null,
+          ...noNodeNfts
+        })
+        .rpc(),
+      "Paused"
+    )
+
+    // buyers of a failed round can always get their payment back
+    const buyerBalanceBefore = await provider.connection.getBalance(referredBuyer.publicKey)
+    await program.methods.refundPresale(1)
+      .accounts({
+        poolState,
+        paymentVault: null,
+        userPaymentAccount: null,
+        user: referredBuyer.publicKey,
+        tokenProgram: null
+      })
+      .signers([referredBuyer])
+      .rpc()
+    const { paidAmount } = await program.account.presalePurchase.fetch(presalePurchaseOf(1, poolState, referredBuyer.publicKey))
+    expect(paidAmount.toNumber()).to.equal(0)
+    expect(await provider.connection.getBalance(referredBuyer.publicKey)).to.be.greaterThan(buyerBalanceBefore)
+
+    console.log("pause tx", pauseTx)
+
+    const unpauseTx = await program.methods.setPaused(0).rpc()
+
+    console.log("unpause tx", unpauseTx)
+  })
+
+  it("wrap a staked node into an NFT, claim and unwrap it!", async () => {
+    const metadataOf = (mintKey: web3.PublicKey) => web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintKey.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    )[0]
+    const masterEditionOf = (mintKey: web3.PublicKey) => web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintKey.toBuffer(), Buffer.from("edition")],
+      TOKEN_METADATA_PROGRAM_ID
+    )[0]
+    const [collectionMint] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("node_collection"), poolState.toBuffer()],
+      program.programId
+    )
+    const collectionMetadata = metadataOf(collectionMint)
+    const collectionMasterEdition = masterEditionOf(collectionMint)
+
+    const collectionTx = await program.methods.initializeNodeCollection("Node", "NODE", "https://ipfs.io/ipfs/QmRtzvCek4tv3u9r1zjEm3wZbuT8MQtaaoAGonZK1CATkB")
+      .accounts({
+        poolState,
+        collectionMint,
+        collectionMetadata,
+        collectionMasterEdition
+      })
+      .rpc()
+
+    const [nodeCollection] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("node_collection_state"), poolState.toBuffer()],
+      program.programId
+    )
+    const nodeCollectionData = await program.account.nodeCollection.fetch(nodeCollection)
+    const [nodeMint] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("node_nft_mint"), poolState.toBuffer(), new BN(nodeCollectionData.mintedNodes).toArrayLike(Buffer, "le", 4)],
+      program.programId
+    )
+    const [nodeNft] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("node_nft"), poolState.toBuffer(), nodeMint.toBuffer()],
+      program.programId
+    )
+    const nodeMetadata = metadataOf(nodeMint)
+    const nodeMasterEdition = masterEditionOf(nodeMint)
+    const userNftAccount = await getAssociatedTokenAddress(nodeMint, provider.publicKey)
+
+    const stakeBefore = await program.account.userStakeEntry.fetch(userStakeEntry)
+
+    const mintTx = await program.methods.mintNodeNft()
+      .accounts({
+        poolState,
+        emissionSchedule: null,
+        collectionMint,
+        collectionMetadata,
+        collectionMasterEdition,
+        nodeMint,
+        nodeMetadata,
+        nodeMasterEdition
+      })
+      .rpc()
+
+    let stakeData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const nodeNftData = await program.account.nodeNft.fetch(nodeNft)
+    expect(stakeData.stakedAmount).to.equal(stakeBefore.stakedAmount - 1)
+    expect(stakeData.wrappedNodes).to.equal(stakeBefore.wrappedNodes + 1)
+    expect(nodeNftData.wrappedBy.toBase58()).to.equal(provider.publicKey.toBase58())
+    expect((await getAccount(provider.connection, userNftAccount)).amount.toString()).to.equal("1")
+
+    // the wrapped node earns on its own, pay it out of a freshly funded vault
+    const holderTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    await program.methods.fundRewards(new BN("100000000000000000"))
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        funderTokenAccount: holderTokenAccount
+      })
+      .rpc()
+
+    const vaultBefore = await tokenBalance(rewardVault)
+    const holderBefore = await tokenBalance(holderTokenAccount)
+
+    const claimTx = await program.methods.claimNodeNft()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        nodeNft,
+        holderNftAccount: userNftAccount,
+        holderTokenAccount
+      })
+      .rpc()
+
+    const claimed = vaultBefore.sub(await tokenBalance(rewardVault))
+    expect(claimed.gtn(0)).to.be.true
+    expect((await tokenBalance(holderTokenAccount)).sub(holderBefore).toString()).to.equal(claimed.toString())
+
+    const burnTx = await program.methods.burnNodeNft()
+      .accounts({
+        poolState,
+        emissionSchedule: null,
+        nodeNft,
+        nodeMint,
+        holderNftAccount: userNftAccount,
+        nodeMetadata,
+        nodeMasterEdition,
+        collectionMetadata,
+        wrapperStakeEntry: null
+      })
+      .rpc()
+
+    stakeData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(stakeData.stakedAmount).to.equal(stakeBefore.stakedAmount)
+    expect(stakeData.wrappedNodes).to.equal(stakeBefore.wrappedNodes)
+    expect(await provider.connection.getAccountInfo(nodeNft)).to.be.null
+
+    console.log("initialize node collection tx", collectionTx)
+    console.log("mint node nft tx", mintTx)
+    console.log("claim node nft tx", claimTx)
+    console.log("burn node nft tx", burnTx)
+  })
+
+  it("vest claimed and compounded rewards!", async () => {
+    const [rewardVesting] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vesting"), poolState.toBuffer(), provider.publicKey.toBuffer()],
+      program.programId
+    )
+    const userTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const sellingVault = await getAssociatedTokenAddress(
+      mint,
+      poolState,
+      true
+    )
+
+    await program.methods.configureVesting(new BN(1), new BN(2), 5000).accounts({ poolState }).rpc()
+
+    const claimTx = await program.methods.claimNodes()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        userTokenAccount
+      })
+      .rpc()
+
+    // a claim only locks the rewards, with the settings of the moment
+    let vestingData = await program.account.rewardVesting.fetch(rewardVesting)
+    let stakeData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(stakeData.claimableAmount.toNumber()).to.equal(0)
+    expect(vestingData.tranches).to.have.length(1)
+    expect(vestingData.tranches[0].cliff.toNumber()).to.equal(1)
+    expect(vestingData.tranches[0].duration.toNumber()).to.equal(2)
+
+    await program.methods.configureVesting(new BN(100), new BN(1000), 5000).accounts({ poolState }).rpc()
+
+    vestingData = await program.account.rewardVesting.fetch(rewardVesting)
+    expect(vestingData.tranches[0].cliff.toNumber()).to.equal(1)
+    expect(vestingData.tranches[0].duration.toNumber()).to.equal(2)
+
+    await waitUntil(vestingData.tranches[0].startAt.toNumber() + 2)
+
+    // compounding locks the fresh rewards too and only spends what already vested
+    const stakeBefore = await program.account.userStakeEntry.fetch(userStakeEntry)
+    const compoundTx = await program.methods.compoundRewards()
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule: null,
+        treasuryVault: userTokenAccount,
+        sellingVault
+      })
+      .rpc()
+
+    vestingData = await program.account.rewardVesting.fetch(rewardVesting)
+    stakeData = await program.account.userStakeEntry.fetch(userStakeEntry)
+    expect(stakeData.claimableAmount.toNumber()).to.equal(0)
+    expect(stakeData.stakedAmount).to.be.greaterThan(stakeBefore.stakedAmount)
+    expect(vestingData.tranches).to.have.length(1)
+    expect(vestingData.tranches[0].cliff.toNumber()).to.equal(100)
+
+    const unlockedAmount = vestingData.unlockedAmount
+    const userBefore = await tokenBalance(userTokenAccount)
+
+    const releaseTx = await program.methods.releaseVested()
+      .accounts({
+        poolState,
+        userTokenAccount
+      })
+      .rpc()
+
+    expect((await tokenBalance(userTokenAccount)).sub(userBefore).toString()).to.equal(unlockedAmount.toString())
+
+    // the provider wallet is also the treasury, so it gets both the payout and the penalty
+    const lockedAmount = vestingData.tranches[0].amount
+    const vaultBefore = await tokenBalance(rewardVault)
+
+    const exitTx = await program.methods.exitVesting()
+      .accounts({
+        poolState,
+        treasuryVault: userTokenAccount,
+        userTokenAccount
+      })
+      .rpc()
+
+    vestingData = await program.account.rewardVesting.fetch(rewardVesting)
+    expect(vestingData.tranches).to.have.length(0)
+    expect(vestingData.unlockedAmount.toNumber()).to.equal(0)
+    expect(vaultBefore.sub(await tokenBalance(rewardVault)).toString()).to.equal(lockedAmount.toString())
+
+    await program.methods.configureVesting(new BN(0), new BN(0), 0).accounts({ poolState }).rpc()
+
+    console.log("claim into vesting tx", claimTx)
+    console.log("compound vested rewards tx", compoundTx)
+    console.log("release vested tx", releaseTx)
+    console.log("exit vesting tx", exitTx)
+  })
+
+  it("presale of a second pool paid with an SPL token!", async () => {
+    const payer = (provider.wallet as anchor.Wallet).payer
+    const poolAuthKeypair = web3.Keypair.fromSecretKey(
+      bs58.decode(
+        "EK8HWgbezLQxgkwZBb4WgQQGUrHvba8QG5pSrbPTfscwY5Hb7RPj1K6FhSDWmYfXNXvFuvtsYAkG1kv17MiNcRa"
+      )
+    )
+
+    // the second pool sells the first pool's token
+    await program.methods.initializePool(sharedPoolId, new BN("1000000000000000"), new BN("10000000000000000000"), 100, 0.2, new BN(0), 5000, new BN(0))
+      .accounts({
+        mint: null,
+        sharedPool: poolState
+      })
+      .rpc()
+
+    const paymentMint = await createMint(provider.connection, payer, provider.publicKey, null, 6)
+    const userPaymentAccount = await createAssociatedTokenAccount(provider.connection, payer, paymentMint, provider.publicKey)
+    await mintTo(provider.connection, payer, paymentMint, userPaymentAccount, provider.publicKey, 1_000_000)
+
+    // rounds keep the mint they were opened with, the first pool already has one
+    await expectError(
+      program.methods.configurePaymentMint().accounts({ poolState, paymentMint }).rpc(),
+      "PresaleAlreadyInitialized"
+    )
+
+    const configureTx = await program.methods.configurePaymentMint()
+      .accounts({
+        poolState: sharedPoolState,
+        paymentMint
+      })
+      .rpc()
+
+    const mintNodesProposal = await createProposal({ mintNodes: { amount: 1000 } }, sharedPoolState)
+    await program.methods.mintNodes(1000)
+      .accounts({
+        poolState: sharedPoolState,
+        proposal: mintNodesProposal
+      })
+      .rpc()
+
+    const pricePerNode = 1000;
+    const presaleStartAt = new BN(await clockNow())
+    const presaleEndAt = presaleStartAt.add(new BN(10))
+    await program.methods.initializePresale(new BN(pricePerNode), 100, presaleStartAt, presaleEndAt, 100, new BN(1000))
+      .accounts({ poolState: sharedPoolState })
+      .rpc()
+    await program.methods.initializeUserStake().accounts({ poolState: sharedPoolState }).rpc()
+
+    const roundPresaleState = presaleStateOf(0, sharedPoolState)
+    const [paymentVault] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("payment_vault"), sharedPoolState.toBuffer()],
+      program.programId
+    )
+    const amount = 5;
+
+    // commission is paid in lamports, so token-paid rounds don't take referrals
+    await program.methods.initializeReferral()
+      .accounts({
+        poolState: sharedPoolState,
+        referrer: referredBuyer.publicKey
+      })
+      .signers([referredBuyer])
+      .rpc()
+    const [sharedReferralAccount] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("referral"), sharedPoolState.toBuffer(), referredBuyer.publicKey.toBuffer()],
+      program.programId
+    )
+    await expectError(
+      program.methods.sellNodesAtPresale(amount, 0, [], new BN(pricePerNode), 0, false)
+        .accounts({
+          poolState: sharedPoolState,
+          presaleState: roundPresaleState,
+          paymentVault,
+          userPaymentAccount,
+          referralAccount: sharedReferralAccount,
+          referralPresale: null,
+          priceFeed: null,
+          tokenProgram: TOKEN_PROGRAM_ID
+        })
+        .rpc(),
+      "ReferralNotSupported"
+    )
+
+    const sellTx = await program.methods.sellNodesAtPresale(amount, 0, [], new BN(pricePerNode), 0, false)
+      .accounts({
+        poolState: sharedPoolState,
+        presaleState: roundPresaleState,
+        paymentVault,
+        userPaymentAccount,
+        referralAccount: null,
+        referralPresale: null,
+        priceFeed: null,
+        tokenProgram: TOKEN_PROGRAM_ID
+      })
+      .rpc()
+
+    const presaleStateData = await program.account.presaleState.fetch(roundPresaleState)
+    expect(presaleStateData.paymentMint.toBase58()).to.equal(paymentMint.toBase58())
+    expect((await getAccount(provider.connection, paymentVault)).amount.toString()).to.equal(String(amount * pricePerNode))
+
+    await waitUntil(presaleEndAt.toNumber())
+
+    // the round raised tokens, so there are no lamports to withdraw
+    const proposal = await createProposal({ withdrawCap: { roundId: 0 } }, sharedPoolState)
+    await expectError(
+      program.methods.withdrawCap(0)
+        .accounts({
+          poolState: sharedPoolState,
+          presaleState: roundPresaleState,
+          proposal,
+          withdrawer: poolAuthKeypair.publicKey
+        })
+        .signers([poolAuthKeypair])
+        .rpc(),
+      "InvalidPaymentMint"
+    )
+
+    const withdrawerPaymentAccount = await createAssociatedTokenAccount(provider.connection, payer, paymentMint, poolAuthKeypair.publicKey)
+    const withdrawTx = await program.methods.withdrawPaymentTokens(0)
+      .accounts({
+        poolState: sharedPoolState,
+        presaleState: roundPresaleState,
+        withdrawerPaymentAccount,
+        withdrawer: poolAuthKeypair.publicKey,
+        proposal
+      })
+      .signers([poolAuthKeypair])
+      .rpc()
+
+    expect((await getAccount(provider.connection, withdrawerPaymentAccount)).amount.toString()).to.equal(String(amount * pricePerNode))
+    expect((await getAccount(provider.connection, paymentVault)).amount.toString()).to.equal("0")
+
+    await program.methods.stakePresaleNodes(0)
+      .accounts({
+        poolState: sharedPoolState,
+        emissionSchedule: null,
+        ...noNodeNfts
+      })
+      .rpc()
+
+    const [sharedStakeEntry] = web3.PublicKey.findProgramAddressSync(
+      [sharedPoolState.toBuffer(), provider.publicKey.toBuffer()],
+      program.programId
+    )
+    expect((await program.account.userStakeEntry.fetch(sharedStakeEntry)).stakedAmount).to.equal(amount)
+
+    console.log("configure payment mint tx", configureTx)
+    console.log("sell nodes for tokens tx", sellTx)
+    console.log("withdraw payment tokens tx", withdrawTx)
+  })
+
+  it("pools sharing a mint share its supply cap!", async () => {
+    const treasuryVault = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    const sellingVault = await getAssociatedTokenAddress(
+      mint,
+      sharedPoolState,
+      true
+    )
+    await program.methods.initializeSellingVault()
+      .accounts({
+        poolState: sharedPoolState,
+        mint,
+        sellingVault,
+        treasury: provider.publicKey,
+        treasuryVault
+      })
+      .rpc()
+
+    const mintTokens = (amount: BN, proposal: web3.PublicKey, mintPool: web3.PublicKey | null) => program.methods.mintTokens(amount)
+      .accounts({
+        poolState: sharedPoolState,
+        mintPool,
+        treasuryVault,
+        sellingVault,
+        proposal
+      })
+      .rpc()
+
+    const amount = new BN("1000000000000000000")
+    const proposal = await createProposal({ mintTokens: { amount } }, sharedPoolState)
+
+    await expectError(mintTokens(amount, proposal, null), "MissingMintPool")
+
+    const ownerBefore = await program.account.poolState.fetch(poolState)
+    const tx = await mintTokens(amount, proposal, poolState)
+
+    // the pool owning the mint keeps the counters, the sharing pool has none
+    const ownerAfter = await program.account.poolState.fetch(poolState)
+    const sharedAfter = await program.account.poolState.fetch(sharedPoolState)
+    const mintedSupply = (pool) => pool.mintedToTreasury.add(pool.mintedToSelling).add(pool.mintedToRewards)
+    expect(mintedSupply(ownerAfter).sub(mintedSupply(ownerBefore)).toString()).to.equal(amount.toString())
+    expect(mintedSupply(sharedAfter).toString()).to.equal("0")
+
+    const overCap = ownerAfter.maxSupply.sub(mintedSupply(ownerAfter)).addn(1)
+    const overCapProposal = await createProposal({ mintTokens: { amount: overCap } }, sharedPoolState)
+    await expectError(mintTokens(overCap, overCapProposal, poolState), "SupplyCapExceeded")
+
+    // a pool with its own mint owns its cap
+    const [ownMintPoolState] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_state"), new BN(2).toArrayLike(Buffer, "le", 2)],
+      program.programId
+    )
+    const [ownMint] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("mint"), ownMintPoolState.toBuffer()],
+      program.programId
+    )
+    await program.methods.initializePool(2, new BN("1000000000000000"), new BN("10000000000000000000"), 100, 0.2, new BN(0), 5000, new BN("1000000000000000000"))
+      .accounts({
+        mint: ownMint,
+        sharedPool: null
+      })
+      .rpc()
+
+    const ownMintPoolData = await program.account.poolState.fetch(ownMintPoolState)
+    expect(ownMintPoolData.mintPool.toBase58()).to.equal(ownMintPoolState.toBase58())
+    expect(ownMintPoolData.sellingMint.toBase58()).to.equal(ownMint.toBase58())
+    expect(sharedAfter.mintPool.toBase58()).to.equal(poolState.toBase58())
+    expect(sharedAfter.sellingMint.toBase58()).to.equal(mint.toBase58())
+
+    console.log("mint tokens for shared pool tx", tx)
+  })
+
+  it("emission schedule drives reward accrual!", async () => {
+    const [emissionSchedule] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("emission_schedule"), poolState.toBuffer()],
+      program.programId
+    )
+    const now = await clockNow()
+    const epochs = [
+      { startAt: new BN(0), rewardPerNode: new BN(2000) },
+      { startAt: new BN(now + 2), rewardPerNode: new BN(500) },
+    ]
+
+    const initTx = await program.methods.initializeEmissionSchedule(epochs, new BN(0))
+      .accounts({
+        poolState
+      })
+      .rpc()
+
+    const before = await program.account.poolState.fetch(poolState)
+    expect(before.emissionSchedule.toBase58()).to.equal(emissionSchedule.toBase58())
+    expect(before.totalStakedNodes).to.be.greaterThan(0)
+
+    await waitUntil(now + 4)
+
+    const funderTokenAccount = await getAssociatedTokenAddress(
+      mint,
+      provider.publicKey
+    )
+    await expectError(
+      program.methods.fundRewards(new BN(1))
+        .accounts({
+          poolState,
+          sellingMint: mint,
+          emissionSchedule: null,
+          funderTokenAccount
+        })
+        .rpc(),
+      "MissingEmissionSchedule"
+    )
+
+    const tx = await program.methods.fundRewards(new BN(1))
+      .accounts({
+        poolState,
+        sellingMint: mint,
+        emissionSchedule,
+        funderTokenAccount
+      })
+      .rpc()
+
+    // every epoch runs until the next one starts, rates are per day and the
+    // accumulator is scaled by the seconds in a day
+    const after = await program.account.poolState.fetch(poolState)
+    const from = before.lastUpdateTs.toNumber()
+    const to = after.lastUpdateTs.toNumber()
+    const expected = epochs.reduce((sum, epoch, index) => {
+      const epochEnd = index + 1 < epochs.length ? epochs[index + 1].startAt.toNumber() : to
+      const start = Math.max(from, epoch.startAt.toNumber())
+      const end = Math.min(to, epochEnd)
+      return end > start ? sum.add(epoch.rewardPerNode.muln(end - start)) : sum
+    }, new BN(0))
+    expect(to).to.be.greaterThan(now + 2)
+    expect(after.accRewardPerNode.sub(before.accRewardPerNode).toString()).to.equal(expected.toString())
+
+    console.log("initialize emission schedule tx", initTx)
+    console.log("fund rewards with emission schedule tx", tx)
+  })
+
+  it("finalize mint!", async () => {
+    const tx = await program.methods.finalizeMint()
+      .accounts({
+        poolState,
+        mint
+      })
+      .rpc()
+
+    const mintAcc = await getMint(provider.connection, mint)
+    expect(mintAcc.mintAuthority).to.be.null
+
+    await expectError(
+      program.methods.finalizeMint().accounts({ poolState, mint }).rpc(),
+      "MintFinalized"
+    )
+
+    console.log("finalize mint tx", tx)
+  })
+
+  it("2 of 3 multisig approves and expires proposals!", async () => {
+    const signer2 = web3.Keypair.generate()
+    const signer3 = web3.Keypair.generate()
+
+    // a lone signer still runs the multisig while it is at threshold 1
+    for (const action of [
+      { addSigner: { signer: signer2.publicKey } },
+      { addSigner: { signer: signer3.publicKey } },
+      { changeThreshold: { threshold: 2 } }
+    ]) {
+      const proposal = await createProposal(action)
+      await program.methods.executeProposal().accounts({ proposal }).rpc()
+    }
+
+    const multisigData = await program.account.multisig.fetch(multisig)
+    expect(multisigData.signers.length).to.equal(3)
+    expect(multisigData.threshold).to.equal(2)
+
+    const amount = 1;
+    const proposal = await createProposal({ mintNodes: { amount } })
+    const mintNodes = () => program.methods.mintNodes(amount).accounts({ poolState, proposal }).rpc()
+
+    // the proposer's approval alone is below the threshold
+    await expectError(mintNodes(), "ProposalNotApproved")
+    await expectError(program.methods.approveProposal().accounts({ proposal }).rpc(), "AlreadyApproved")
+
+    await program.methods.approveProposal()
+      .accounts({ proposal, signer: signer2.publicKey })
+      .signers([signer2])
+      .rpc()
+
+    const poolNodesBefore = (await program.account.poolState.fetch(poolState)).totalNodes
+    const tx = await mintNodes()
+    expect((await program.account.poolState.fetch(poolState)).totalNodes).to.equal(poolNodesBefore + amount)
+
+    // nobody can approve a proposal once it expired
+    const multisigCount = (await program.account.multisig.fetch(multisig)).proposalCount
+    const [expiringProposal] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal"), multisigCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )
+    const expiresAt = await clockNow() + 2
+    await program.methods.createProposal(poolState, { mintNodes: { amount } }, new BN(expiresAt))
+      .accounts({ proposal: expiringProposal })
+      .rpc()
+    await waitUntil(expiresAt)
+    await expectError(
+      program.methods.approveProposal()
+        .accounts({ proposal: expiringProposal, signer: signer2.publicKey })
+        .signers([signer2])
+        .rpc(),
+      "ProposalExpired"
+    )
+
+    const restoreProposal = await createProposal({ changeThreshold: { threshold: 1 } })
+    await program.methods.approveProposal()
+      .accounts({ proposal: restoreProposal, signer: signer2.publicKey })
+      .signers([signer2])
+      .rpc()
+    await program.methods.executeProposal().accounts({ proposal: restoreProposal }).rpc()
+
+    expect((await program.account.multisig.fetch(multisig)).threshold).to.equal(1)
+
+    console.log("2 of 3 mint nodes tx", tx)
+  })
+
   it("Accounts data", async () => {
     const data = {}
     data.totalNodes = (await program.account.poolState.fetch(poolState)).totalNodes;